@@ -1,4 +1,5 @@
 use ugradrs::draw_dot::draw_dot;
+use ugradrs::nn::activation::Activation;
 use ugradrs::nn::Neuron;
 use ugradrs::value::Value;
 
@@ -8,7 +9,7 @@ fn main() {
     y.backward();
     draw_dot(y, "relu.dot").expect("Failed to create graph");
 
-    let n: Neuron<2> = Neuron::new(false);
+    let n: Neuron<2> = Neuron::new(Activation::Relu);
     let y = n.forward([1.0.into(), (-2.0).into()]);
     y.backward();
     draw_dot(y, "neuron.dot").expect("Failed to create graph");
@@ -0,0 +1,219 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
+
+use crate::value::{Operation, Value};
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static ANOMALY: RefCell<Option<Anomaly>> = const { RefCell::new(None) };
+}
+
+/// The pass over the graph in which an anomaly occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Forward,
+    Backward,
+}
+
+/// The first non-finite data or gradient produced while anomaly detection was enabled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub pass: Pass,
+    /// Operation that produced the non-finite number
+    pub operation: Operation,
+    /// Data of each input to the operation
+    pub inputs: Vec<f64>,
+    /// The non-finite data (forward) or gradient (backward) that was produced
+    pub value: f64,
+    /// Label of the offending node (if any) followed by the labels of the
+    /// nearest labelled Values it was computed from
+    pub labels: Vec<String>,
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pass = match self.pass {
+            Pass::Forward => "data",
+            Pass::Backward => "gradient",
+        };
+        write!(
+            f,
+            "{} {pass} produced by {:?} with inputs {:?}",
+            self.value, self.operation, self.inputs
+        )?;
+        if !self.labels.is_empty() {
+            write!(f, " from [{}]", self.labels.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Anomaly {}
+
+/// Enable or disable anomaly detection for the current thread
+///
+/// While enabled every operation and backward step checks for NaN or
+/// infinity, keeping the first occurrence for `take_anomaly`
+pub fn set_detect_anomaly(enabled: bool) {
+    ENABLED.with(|e| e.set(enabled))
+}
+
+/// Whether anomaly detection is enabled for the current thread
+pub fn is_anomaly_detection_enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+/// Retrieve and clear the first anomaly detected on the current thread
+pub fn take_anomaly() -> Option<Anomaly> {
+    ANOMALY.with(|a| a.borrow_mut().take())
+}
+
+/// Restores the previous enabled state when dropped, even while unwinding
+struct RestoreEnabled(bool);
+
+impl Drop for RestoreEnabled {
+    fn drop(&mut self) {
+        set_detect_anomaly(self.0);
+    }
+}
+
+/// Run a closure with anomaly detection enabled
+///
+/// Returns the first anomaly detected, if any, in place of the result
+pub fn detect_anomaly<T>(f: impl FnOnce() -> T) -> Result<T, Anomaly> {
+    let guard = RestoreEnabled(is_anomaly_detection_enabled());
+    take_anomaly();
+    set_detect_anomaly(true);
+    let result = f();
+    drop(guard);
+    match take_anomaly() {
+        Some(anomaly) => Err(anomaly),
+        None => Ok(result),
+    }
+}
+
+/// Label of a node followed by the nearest labelled Values it depends on
+fn label_chain(node: &Value) -> Vec<String> {
+    let mut labels: Vec<String> = node.label().into_iter().collect();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from(node.children());
+    while let Some(v) = queue.pop_front() {
+        if visited.insert(v.clone()) {
+            match v.label() {
+                Some(label) => labels.push(label),
+                None => queue.extend(v.children()),
+            }
+        }
+    }
+    labels
+}
+
+fn record(anomaly: impl FnOnce() -> Anomaly) {
+    ANOMALY.with(|a| {
+        let mut slot = a.borrow_mut();
+        if slot.is_none() {
+            *slot = Some(anomaly())
+        }
+    })
+}
+
+/// Check a node after its data was computed by an operation
+pub(crate) fn check_forward(node: &Value) {
+    if !is_anomaly_detection_enabled() {
+        return;
+    }
+    let (Some(operation), data) = (node.operation(), node.data()) else {
+        return;
+    };
+    let inputs: Vec<f64> = node.children().iter().map(|c| c.data()).collect();
+    if !data.is_finite() && inputs.iter().all(|i| i.is_finite()) {
+        record(|| Anomaly {
+            pass: Pass::Forward,
+            operation,
+            inputs,
+            value: data,
+            labels: label_chain(node),
+        })
+    }
+}
+
+/// Check the children of a node after its gradient was propagated to them
+pub(crate) fn check_backward(node: &Value) {
+    if !is_anomaly_detection_enabled() || !node.gradient().is_finite() {
+        return;
+    }
+    let Some(operation) = node.operation() else {
+        return;
+    };
+    let children = node.children();
+    if let Some(child) = children.iter().find(|c| !c.gradient().is_finite()) {
+        record(|| Anomaly {
+            pass: Pass::Backward,
+            operation,
+            inputs: children.iter().map(|c| c.data()).collect(),
+            value: child.gradient(),
+            labels: label_chain(node),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_anomaly, is_anomaly_detection_enabled, take_anomaly, Anomaly, Pass};
+    use crate::value::{Operation, Value};
+
+    #[test]
+    fn test_forward_anomaly() {
+        let x = Value::named("x", 1000.0);
+        let result = detect_anomaly(|| {
+            let e = (x.clone() * 2.0).with_label("scaled").exp();
+            // Subsequent operations propagate the infinity but did not produce it
+            (e.clone() - e).tanh()
+        });
+        assert_eq!(
+            result,
+            Err(Anomaly {
+                pass: Pass::Forward,
+                operation: Operation::Exponent,
+                inputs: vec![2000.0],
+                value: f64::INFINITY,
+                labels: vec!["scaled".to_string()],
+            })
+        );
+        assert!(!is_anomaly_detection_enabled());
+        assert_eq!(take_anomaly(), None);
+    }
+
+    #[test]
+    fn test_backward_anomaly() {
+        let x = Value::named("x", 0.0);
+        let y = x.clone().powf(0.5.into()).with_label("root");
+        let anomaly = detect_anomaly(|| y.backward()).unwrap_err();
+        assert_eq!(anomaly.pass, Pass::Backward);
+        assert_eq!(anomaly.operation, Operation::Pow);
+        assert_eq!(anomaly.inputs, vec![0.0, 0.5]);
+        assert_eq!(anomaly.value, f64::INFINITY);
+        assert_eq!(anomaly.labels, vec!["root".to_string(), "x".to_string()]);
+        assert_eq!(
+            anomaly.to_string(),
+            "inf gradient produced by Pow with inputs [0.0, 0.5] from [root, x]"
+        );
+    }
+
+    #[test]
+    fn test_no_anomaly() {
+        let x = Value::from(2.0);
+        assert!(detect_anomaly(|| (x.clone() * 3.0).tanh().backward()).is_ok());
+        // Nothing is recorded when detection is disabled
+        (Value::from(f64::MAX) * 2.0).exp();
+        assert_eq!(take_anomaly(), None);
+    }
+
+    #[test]
+    fn test_panic_restores_state() {
+        let result = std::panic::catch_unwind(|| detect_anomaly(|| panic!("failed")));
+        assert!(result.is_err());
+        assert!(!is_anomaly_detection_enabled());
+    }
+}
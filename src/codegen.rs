@@ -0,0 +1,452 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::value::{Operation, Value};
+
+/// Target language of generated source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    C,
+}
+
+impl Language {
+    /// Words that can not be used as the name of the generated function
+    fn reserved(self) -> &'static str {
+        match self {
+            Language::Rust => {
+                "_ abstract as async await become box break const continue crate do dyn else \
+                 enum extern false final fn for if impl in let loop macro match mod move mut \
+                 override priv pub ref return self Self static struct super trait true try type \
+                 typeof unsafe unsized use virtual where while yield"
+            }
+            // Keywords up to C23, and the math.h functions the source calls
+            Language::C => {
+                "alignas alignof auto bool break case char const constexpr continue default do \
+                 double else enum extern false float for goto if inline int long nullptr \
+                 register restrict return short signed sizeof static static_assert struct \
+                 switch thread_local true typedef typeof typeof_unqual union unsigned void \
+                 volatile while _Alignas _Alignof _Atomic _BitInt _Bool _Complex _Decimal128 \
+                 _Decimal32 _Decimal64 _Generic _Imaginary _Noreturn _Static_assert \
+                 _Thread_local exp fabs fmax log pow tanh"
+            }
+        }
+    }
+}
+
+/// Error raised when a graph can not be turned into source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodegenError {
+    /// The function name is not a valid identifier or is reserved in the
+    /// target language
+    InvalidName(String),
+    /// The input at this index is the result of an operation
+    InputNotLeaf(usize),
+    /// The input at this index was already provided
+    DuplicateInput(usize),
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodegenError::InvalidName(name) => write!(f, "'{name}' is not a valid identifier"),
+            CodegenError::InputNotLeaf(i) => write!(f, "input {i} is not a leaf Value"),
+            CodegenError::DuplicateInput(i) => write!(f, "input {i} is provided more than once"),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// A literal for a constant in the target language
+fn literal(value: f64, language: Language) -> String {
+    match (language, value) {
+        // Typed, so that constants can be the receiver of method calls
+        (Language::Rust, v) if v.is_finite() => format!("{v:?}_f64"),
+        (Language::C, v) if v.is_finite() => format!("{v:?}"),
+        (Language::Rust, v) if v.is_nan() => "f64::NAN".to_string(),
+        (Language::Rust, v) if v > 0. => "f64::INFINITY".to_string(),
+        (Language::Rust, _) => "f64::NEG_INFINITY".to_string(),
+        (Language::C, v) if v.is_nan() => "NAN".to_string(),
+        (Language::C, v) if v > 0. => "INFINITY".to_string(),
+        (Language::C, _) => "(-INFINITY)".to_string(),
+    }
+}
+
+/// Call a single argument math function in the target language
+fn call(function: &str, argument: &str, language: Language) -> String {
+    match language {
+        Language::Rust => format!("{argument}.{function}()"),
+        Language::C => format!("{function}({argument})"),
+    }
+}
+
+fn pow(base: &str, exponent: &str, language: Language) -> String {
+    match language {
+        Language::Rust => format!("{base}.powf({exponent})"),
+        Language::C => format!("pow({base}, {exponent})"),
+    }
+}
+
+/// Expression computing a node from the variables holding its children
+fn forward(operation: Operation, children: &[String], language: Language) -> String {
+    match operation {
+        Operation::Add => children.join(" + "),
+        Operation::Subtract => format!("{} - {}", children[0], children[1]),
+        Operation::Negate => format!("-{}", children[0]),
+        Operation::Multiply => children.join(" * "),
+        Operation::Divide => format!("{} / {}", children[0], children[1]),
+        Operation::Sum | Operation::Dot if children.is_empty() => "0.0".to_string(),
+        Operation::Sum => children.join(" + "),
+        Operation::Dot => {
+            let (a, b) = children.split_at(children.len() / 2);
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| format!("{a} * {b}"))
+                .collect::<Vec<String>>()
+                .join(" + ")
+        }
+        Operation::Tanh => call("tanh", &children[0], language),
+        Operation::Exponent => call("exp", &children[0], language),
+        Operation::Abs => match language {
+            Language::Rust => call("abs", &children[0], language),
+            Language::C => call("fabs", &children[0], language),
+        },
+        Operation::Log => match language {
+            Language::Rust => call("ln", &children[0], language),
+            Language::C => call("log", &children[0], language),
+        },
+        Operation::Pow => pow(&children[0], &children[1], language),
+        Operation::Relu => match language {
+            Language::Rust => format!("{}.max(0.0)", children[0]),
+            Language::C => format!("fmax({}, 0.0)", children[0]),
+        },
+    }
+}
+
+/// Expressions for the contribution of a node's adjoint to each child's adjoint
+///
+/// Mirrors the gradient calculation of `Value::backward`
+fn backward(
+    operation: Operation,
+    node: &str,
+    children: &[String],
+    adjoint: &str,
+    language: Language,
+) -> Vec<Option<String>> {
+    match operation {
+        Operation::Add | Operation::Sum => {
+            children.iter().map(|_| Some(adjoint.to_string())).collect()
+        }
+        Operation::Dot => {
+            let n = children.len() / 2;
+            (0..children.len())
+                .map(|i| Some(format!("{} * {adjoint}", children[(i + n) % (2 * n)])))
+                .collect()
+        }
+        Operation::Subtract => vec![Some(adjoint.to_string()), Some(format!("-{adjoint}"))],
+        Operation::Negate => vec![Some(format!("-{adjoint}"))],
+        Operation::Multiply => vec![
+            Some(format!("{} * {adjoint}", children[1])),
+            Some(format!("{} * {adjoint}", children[0])),
+        ],
+        Operation::Divide => vec![
+            Some(format!("{adjoint} / {}", children[1])),
+            Some(format!(
+                "-{} / ({} * {}) * {adjoint}",
+                children[0], children[1], children[1]
+            )),
+        ],
+        Operation::Tanh => vec![Some(format!("(1.0 - {node} * {node}) * {adjoint}"))],
+        Operation::Exponent => vec![Some(format!("{node} * {adjoint}"))],
+        Operation::Log => vec![Some(format!("{adjoint} / {}", children[0]))],
+        Operation::Abs => {
+            let x = &children[0];
+            vec![Some(match language {
+                Language::Rust => format!(
+                    "if {x} > 0.0 {{ {adjoint} }} else if {x} < 0.0 {{ -{adjoint} }} else {{ 0.0 }}"
+                ),
+                Language::C => format!("({x} > 0.0 ? {adjoint} : ({x} < 0.0 ? -{adjoint} : 0.0))"),
+            })]
+        }
+        Operation::Pow => vec![
+            Some(format!(
+                "{} * {} * {adjoint}",
+                children[1],
+                pow(&children[0], &format!("{} - 1.0", children[1]), language)
+            )),
+            None,
+        ],
+        Operation::Relu => vec![Some(match language {
+            Language::Rust => format!("if {node} > 0.0 {{ {adjoint} }} else {{ 0.0 }}"),
+            Language::C => format!("({node} > 0.0 ? {adjoint} : 0.0)"),
+        })],
+    }
+}
+
+/// Generate standalone source for a function computing `output` and its
+/// gradient with respect to each of `inputs`
+///
+/// Every leaf of the graph that is not one of the inputs is treated as a
+/// constant with its current data. The generated Rust function has the
+/// signature `fn name(x: &[f64; N]) -> (f64, [f64; N])` while the C function
+/// is `double name(const double x[N], double grad[N])`, writing the gradient
+/// to `grad` and returning the output. Neither depends on ugradrs.
+pub fn generate(
+    output: &Value,
+    inputs: &[Value],
+    name: &str,
+    language: Language,
+) -> Result<String, CodegenError> {
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        || language
+            .reserved()
+            .split_whitespace()
+            .any(|word| word == name)
+    {
+        return Err(CodegenError::InvalidName(name.to_string()));
+    }
+    let mut input_index = HashMap::new();
+    for (i, input) in inputs.iter().enumerate() {
+        if input.operation().is_some() {
+            return Err(CodegenError::InputNotLeaf(i));
+        }
+        if input_index.insert(input.clone(), i).is_some() {
+            return Err(CodegenError::DuplicateInput(i));
+        }
+    }
+
+    let topo = output.topological_order();
+    let variable: HashMap<Value, String> = topo
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.clone(), format!("v{i}")))
+        .collect();
+    // Only nodes that depend on an input need an adjoint
+    let mut active = HashSet::new();
+    for node in topo.iter() {
+        if input_index.contains_key(node) || node.children().iter().any(|c| active.contains(c)) {
+            active.insert(node.clone());
+        }
+    }
+    let adjoint = |v: &Value| format!("g{}", &variable[v][1..]);
+    let declare = match language {
+        Language::Rust => "let",
+        Language::C => "const double",
+    };
+    let declare_mut = match language {
+        Language::Rust => "let mut",
+        Language::C => "double",
+    };
+
+    let mut body = Vec::new();
+    body.push("// Forward pass".to_string());
+    for node in topo.iter() {
+        let expression = match (node.operation(), input_index.get(node)) {
+            (_, Some(i)) => format!("x[{i}]"),
+            (Some(op), None) => {
+                let children: Vec<String> = node
+                    .children()
+                    .iter()
+                    .map(|c| variable[c].clone())
+                    .collect();
+                forward(op, &children, language)
+            }
+            (None, None) => literal(node.data(), language),
+        };
+        body.push(format!("{declare} {} = {expression};", variable[node]));
+    }
+
+    body.push("// Backward pass".to_string());
+    if active.contains(output) {
+        for node in topo.iter().filter(|n| active.contains(*n)) {
+            if Rc::ptr_eq(node, output) {
+                body.push(format!("{declare} {} = 1.0;", adjoint(node)));
+            } else {
+                body.push(format!("{declare_mut} {} = 0.0;", adjoint(node)));
+            }
+        }
+    }
+    for node in topo.iter().rev().filter(|n| active.contains(*n)) {
+        if let Some(op) = node.operation() {
+            let children = node.children();
+            let names: Vec<String> = children.iter().map(|c| variable[c].clone()).collect();
+            let terms = backward(op, &variable[node], &names, &adjoint(node), language);
+            for (child, term) in children.iter().zip(terms) {
+                if let (true, Some(term)) = (active.contains(child), term) {
+                    body.push(format!("{} += {term};", adjoint(child)));
+                }
+            }
+        }
+    }
+
+    let gradients: Vec<String> = inputs
+        .iter()
+        .map(|input| {
+            if active.contains(input) {
+                adjoint(input)
+            } else {
+                "0.0".to_string()
+            }
+        })
+        .collect();
+    let n = inputs.len();
+    let result = &variable[output];
+    let mut source = String::new();
+    match language {
+        Language::Rust => {
+            source.push_str(&format!(
+                "/// Computes the output and its gradient with respect to each input\n\
+                 pub fn {name}(x: &[f64; {n}]) -> (f64, [f64; {n}]) {{\n"
+            ));
+            for line in body {
+                source.push_str(&format!("    {line}\n"));
+            }
+            source.push_str(&format!("    ({result}, [{}])\n}}\n", gradients.join(", ")));
+        }
+        Language::C => {
+            source.push_str(&format!(
+                "#include <math.h>\n\n\
+                 /* Computes the output, writing its gradient with respect to each input to grad */\n\
+                 double {name}(const double x[{n}], double grad[{n}]) {{\n"
+            ));
+            for line in body {
+                match line.strip_prefix("// ") {
+                    Some(comment) => source.push_str(&format!("    /* {comment} */\n")),
+                    None => source.push_str(&format!("    {line}\n")),
+                }
+            }
+            for (i, gradient) in gradients.iter().enumerate() {
+                source.push_str(&format!("    grad[{i}] = {gradient};\n"));
+            }
+            source.push_str(&format!("    return {result};\n}}\n"));
+        }
+    }
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, CodegenError, Language};
+    use crate::value::Value;
+    use std::process::Command;
+
+    #[test]
+    fn test_generate_rust() {
+        let x = Value::from(2.0);
+        let y = (x.clone() * 3.0).relu();
+        assert_eq!(
+            generate(&y, &[x], "f", Language::Rust).unwrap(),
+            "/// Computes the output and its gradient with respect to each input\n\
+             pub fn f(x: &[f64; 1]) -> (f64, [f64; 1]) {\n    \
+                 // Forward pass\n    \
+                 let v0 = x[0];\n    \
+                 let v1 = 3.0_f64;\n    \
+                 let v2 = v0 * v1;\n    \
+                 let v3 = v2.max(0.0);\n    \
+                 // Backward pass\n    \
+                 let mut g0 = 0.0;\n    \
+                 let mut g2 = 0.0;\n    \
+                 let g3 = 1.0;\n    \
+                 g2 += if v3 > 0.0 { g3 } else { 0.0 };\n    \
+                 g0 += v1 * g2;\n    \
+                 (v3, [g0])\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_c() {
+        let x = Value::from(2.0);
+        let c = Value::from(f64::INFINITY);
+        let y = x.clone().tanh() + c;
+        let source = generate(&y, &[x, Value::from(1.0)], "f", Language::C).unwrap();
+        assert!(source.contains("double f(const double x[2], double grad[2]) {"));
+        assert!(source.contains("const double v1 = tanh(v0);"));
+        assert!(source.contains("const double v2 = INFINITY;"));
+        assert!(source.contains("g0 += (1.0 - v1 * v1) * g1;"));
+        assert!(source.contains("grad[1] = 0.0;"));
+        assert!(source.contains("return v3;"));
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        let x = Value::from(2.0);
+        let y = x.clone() * x.clone();
+        assert_eq!(
+            generate(&y, std::slice::from_ref(&x), "1f", Language::Rust),
+            Err(CodegenError::InvalidName("1f".to_string()))
+        );
+        for (name, language) in [
+            ("fn", Language::Rust),
+            ("match", Language::Rust),
+            ("double", Language::C),
+            ("int", Language::C),
+            ("exp", Language::C),
+        ] {
+            assert_eq!(
+                generate(&y, std::slice::from_ref(&x), name, language),
+                Err(CodegenError::InvalidName(name.to_string()))
+            );
+        }
+        // Reserved in one language only
+        assert!(generate(&y, std::slice::from_ref(&x), "double", Language::Rust).is_ok());
+        assert!(generate(&y, std::slice::from_ref(&x), "match", Language::C).is_ok());
+        assert_eq!(
+            generate(&y, std::slice::from_ref(&y), "f", Language::Rust),
+            Err(CodegenError::InputNotLeaf(0))
+        );
+        assert_eq!(
+            generate(&y, &[x.clone(), x], "f", Language::Rust),
+            Err(CodegenError::DuplicateInput(1))
+        );
+    }
+
+    #[test]
+    fn test_generated_rust_matches_backward() {
+        let a = Value::from(0.3);
+        let b = Value::from(-1.2);
+        let y = (a.clone() * b.clone() + a.clone().exp()).tanh() * b.clone().powf(2.0.into())
+            + (b.clone() + 0.5).relu()
+            + Value::dot(&[a.clone(), b.clone()], &[b.clone(), Value::from(3.0)])
+            + [a.clone(), b.clone(), a.clone()].into_iter().sum::<Value>()
+            + (a.clone() - b.clone()) / -(b.clone() * a.clone())
+            + (b.clone() * b.clone()).ln()
+            + (a.clone() + b.clone()).abs()
+            + Value::from(3.0).tanh() * a.clone()
+            + -Value::from(2.0).exp() * b.clone();
+        y.backward();
+
+        let dir = std::env::temp_dir().join(format!("ugradrs-codegen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.rs");
+        let binary = dir.join("main");
+        std::fs::write(
+            &source,
+            generate(&y, &[a.clone(), b.clone()], "f", Language::Rust).unwrap()
+                + "fn main() {\n    \
+                       let (v, g) = f(&[0.3, -1.2]);\n    \
+                       println!(\"{v:?} {:?} {:?}\", g[0], g[1]);\n\
+                   }\n",
+        )
+        .unwrap();
+        let status = Command::new("rustc")
+            .arg(&source)
+            .arg("-o")
+            .arg(&binary)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = Command::new(&binary).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        let result: Vec<f64> = output
+            .split_whitespace()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(result, vec![y.data(), a.gradient(), b.gradient()]);
+    }
+}
@@ -0,0 +1,231 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::value::Value;
+
+/// A collection of `(input, target)` samples
+pub trait Dataset<const I: usize, const O: usize> {
+    fn len(&self) -> usize;
+
+    /// The sample at an index, which must be less than `len`
+    fn get(&self, index: usize) -> ([f64; I], [f64; O]);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<const I: usize, const O: usize> Dataset<I, O> for [([f64; I], [f64; O])] {
+    fn len(&self) -> usize {
+        <[_]>::len(self)
+    }
+
+    fn get(&self, index: usize) -> ([f64; I], [f64; O]) {
+        self[index]
+    }
+}
+
+impl<const I: usize, const O: usize> Dataset<I, O> for Vec<([f64; I], [f64; O])> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn get(&self, index: usize) -> ([f64; I], [f64; O]) {
+        self[index]
+    }
+}
+
+/// Samples held in memory, such as those created by the `datasets` module
+pub type Samples<const I: usize, const O: usize> = Vec<([f64; I], [f64; O])>;
+
+/// A minibatch of inputs, ready for `Mlp::forward`, and their targets
+pub type Batch<const I: usize, const O: usize> = Vec<([Value; I], [Value; O])>;
+
+/// Splits a Dataset into minibatches of Values, once per epoch
+///
+/// Samples are taken in order unless shuffling or weighted sampling is enabled.
+/// Weighted sampling draws `len` samples with replacement, each with
+/// probability proportional to its weight.
+pub struct DataLoader<'a, const I: usize, const O: usize> {
+    dataset: &'a dyn Dataset<I, O>,
+    batch_size: usize,
+    shuffle: bool,
+    drop_last: bool,
+    weights: Option<WeightedIndex<f64>>,
+    rng: StdRng,
+}
+
+impl<'a, const I: usize, const O: usize> DataLoader<'a, I, O> {
+    pub fn new(dataset: &'a dyn Dataset<I, O>, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "Batch size must be positive");
+        Self {
+            dataset,
+            batch_size,
+            shuffle: false,
+            drop_last: false,
+            weights: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Visit the samples in a new random order each epoch
+    pub fn with_shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    /// Seed the random number generator used for shuffling and sampling
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Skip the final batch of each epoch if it is smaller than the batch size
+    pub fn with_drop_last(mut self, drop_last: bool) -> Self {
+        self.drop_last = drop_last;
+        self
+    }
+
+    /// Draw samples with replacement in proportion to a weight per sample
+    ///
+    /// Panics if the number of weights does not match the dataset, or they do
+    /// not include a positive weight.
+    pub fn with_weights(mut self, weights: &[f64]) -> Self {
+        assert_eq!(
+            weights.len(),
+            self.dataset.len(),
+            "Expected a weight for each sample"
+        );
+        self.weights = Some(WeightedIndex::new(weights).expect("Invalid sample weights"));
+        self
+    }
+
+    /// Number of batches in each epoch
+    pub fn len(&self) -> usize {
+        let n = self.dataset.len();
+        if self.drop_last {
+            n / self.batch_size
+        } else {
+            n.div_ceil(self.batch_size)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Dataset indices for the next epoch
+    fn indices(&mut self) -> Vec<usize> {
+        let n = self.dataset.len();
+        match &self.weights {
+            Some(weights) => weights.sample_iter(&mut self.rng).take(n).collect(),
+            None => {
+                let mut indices: Vec<usize> = (0..n).collect();
+                if self.shuffle {
+                    indices.shuffle(&mut self.rng);
+                }
+                indices
+            }
+        }
+    }
+
+    /// The batches of the next epoch
+    pub fn batches(&mut self) -> impl Iterator<Item = Batch<I, O>> + '_ {
+        let indices = self.indices();
+        let (batches, batch_size) = (self.len(), self.batch_size);
+        let dataset = self.dataset;
+        (0..batches).map(move |b| {
+            indices
+                .iter()
+                .skip(b * batch_size)
+                .take(batch_size)
+                .map(|&i| {
+                    let (x, y) = dataset.get(i);
+                    (x.map(Value::from), y.map(Value::from))
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataLoader, Dataset};
+
+    fn dataset() -> Vec<([f64; 1], [f64; 1])> {
+        (0..10).map(|i| ([i as f64], [-(i as f64)])).collect()
+    }
+
+    /// Inputs of each batch in the next epoch
+    fn epoch(loader: &mut DataLoader<1, 1>) -> Vec<Vec<f64>> {
+        loader
+            .batches()
+            .map(|b| b.iter().map(|(x, _)| x[0].data()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_sequential() {
+        let data = dataset();
+        let mut loader = DataLoader::new(&data, 4);
+        assert_eq!(loader.len(), 3);
+        assert_eq!(
+            epoch(&mut loader),
+            vec![
+                vec![0.0, 1.0, 2.0, 3.0],
+                vec![4.0, 5.0, 6.0, 7.0],
+                vec![8.0, 9.0]
+            ]
+        );
+        let batch = loader.batches().next().unwrap();
+        assert_eq!(batch[3].1[0].data(), -3.0);
+
+        let mut loader = DataLoader::new(&data, 4).with_drop_last(true);
+        assert_eq!(loader.len(), 2);
+        assert_eq!(epoch(&mut loader).concat().len(), 8);
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let data = dataset();
+        let mut loader = DataLoader::new(&data, 3).with_shuffle(true).with_seed(7);
+        let first = epoch(&mut loader);
+        let second = epoch(&mut loader);
+        assert_ne!(first, second);
+        for e in [&first, &second] {
+            let mut seen = e.concat();
+            seen.sort_by(f64::total_cmp);
+            assert_eq!(seen, (0..10).map(|i| i as f64).collect::<Vec<_>>());
+        }
+
+        // The same seed gives the same order
+        let mut again = DataLoader::new(&data, 3).with_shuffle(true).with_seed(7);
+        assert_eq!(epoch(&mut again), first);
+    }
+
+    #[test]
+    fn test_weighted_sampling() {
+        let data = dataset();
+        let mut weights = vec![0.0; 10];
+        weights[2] = 1.0;
+        weights[5] = 3.0;
+        let mut loader = DataLoader::new(&data, 5)
+            .with_weights(&weights)
+            .with_seed(0);
+        let samples: Vec<f64> = (0..20).flat_map(|_| epoch(&mut loader).concat()).collect();
+        assert_eq!(samples.len(), 200);
+        assert!(samples.iter().all(|s| *s == 2.0 || *s == 5.0));
+        let fives = samples.iter().filter(|s| **s == 5.0).count();
+        assert!(fives > 120 && fives < 180, "{fives} samples of 5");
+    }
+
+    #[test]
+    fn test_slice_dataset() {
+        let data = dataset();
+        let slice: &[([f64; 1], [f64; 1])] = &data[2..5];
+        assert_eq!(Dataset::len(slice), 3);
+        assert_eq!(Dataset::get(slice, 0), ([2.0], [-2.0]));
+    }
+}
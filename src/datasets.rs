@@ -0,0 +1,265 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use std::f64::consts::PI;
+
+/// Normal distribution of noise, panicking on an invalid standard deviation
+fn noise(stddev: f64) -> Normal<f64> {
+    Normal::new(0., stddev).expect("Noise standard deviation must be finite and non-negative")
+}
+
+/// Add noise to each point of a binary classification dataset and shuffle it
+fn finish(
+    points: impl Iterator<Item = ([f64; 2], f64)>,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 2], [f64; 1])> {
+    let noise = noise(noise_stddev);
+    let mut data: Vec<_> = points
+        .map(|(x, t)| (x.map(|x| x + noise.sample(rng)), [t]))
+        .collect();
+    data.shuffle(rng);
+    data
+}
+
+/// Two interwoven half-circles
+///
+/// Based on the scikit-learn `make_moons` method. The lower moon has a target
+/// of -1 and the upper moon 1.
+///
+/// # Arguments
+///
+/// * `n_samples` - Total number of samples, split evenly between the moons
+/// * `noise_stddev` - Standard deviation of normal distribution noise added on top of the crescent values
+/// * `rng` - Random number generator used to create the noise
+pub fn make_moons(
+    n_samples: usize,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 2], [f64; 1])> {
+    let n_outer = n_samples / 2;
+    let n_inner = n_samples - n_outer;
+    let outer = (0..n_outer).map(|s| {
+        let r = s as f64 * PI / n_outer as f64;
+        ([r.cos(), r.sin()], -1.)
+    });
+    let inner = (0..n_inner).map(|s| {
+        let r = s as f64 * PI / n_inner as f64;
+        ([1.0 - r.cos(), 1.0 - r.sin() - 0.5], 1.)
+    });
+    finish(outer.chain(inner), noise_stddev, rng)
+}
+
+/// A small circle, target 1, inside a unit circle, target -1
+///
+/// # Arguments
+///
+/// * `n_samples` - Total number of samples, split evenly between the circles
+/// * `factor` - Radius of the inner circle relative to the outer circle
+/// * `noise_stddev` - Standard deviation of normal distribution noise added to each point
+/// * `rng` - Random number generator used to create the noise
+pub fn make_circles(
+    n_samples: usize,
+    factor: f64,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 2], [f64; 1])> {
+    let n_outer = n_samples / 2;
+    let n_inner = n_samples - n_outer;
+    let circle = |n: usize, radius: f64, target: f64| {
+        (0..n).map(move |s| {
+            let r = 2. * PI * s as f64 / n as f64;
+            ([radius * r.cos(), radius * r.sin()], target)
+        })
+    };
+    finish(
+        circle(n_outer, 1., -1.).chain(circle(n_inner, factor, 1.)),
+        noise_stddev,
+        rng,
+    )
+}
+
+/// Compile time check that there is at least one cluster
+struct Clusters<const C: usize>;
+
+impl<const C: usize> Clusters<C> {
+    const NON_EMPTY: () = assert!(C > 0, "make_blobs needs at least one cluster");
+}
+
+/// Normally distributed clusters around `C` random centers, with one-hot targets
+///
+/// `C` must be at least one, which is checked at compile time:
+///
+/// ```compile_fail
+/// let blobs = ugradrs::datasets::make_blobs::<0>(10, 1.0, &mut rand::thread_rng());
+/// ```
+///
+/// # Arguments
+///
+/// * `n_samples` - Total number of samples, split as evenly as possible between the clusters
+/// * `cluster_stddev` - Standard deviation of each cluster
+/// * `rng` - Random number generator used to place the centers and samples
+pub fn make_blobs<const C: usize>(
+    n_samples: usize,
+    cluster_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 2], [f64; C])> {
+    let () = Clusters::<C>::NON_EMPTY;
+    let centers: Vec<[f64; 2]> = (0..C)
+        .map(|_| [rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0)])
+        .collect();
+    let noise = noise(cluster_stddev);
+    let mut data: Vec<_> = (0..n_samples)
+        .map(|s| {
+            let class = s % C;
+            let mut target = [0.; C];
+            target[class] = 1.;
+            (centers[class].map(|c| c + noise.sample(rng)), target)
+        })
+        .collect();
+    data.shuffle(rng);
+    data
+}
+
+/// Two interleaved spirals, each making one and a half turns around the origin
+///
+/// # Arguments
+///
+/// * `n_samples` - Total number of samples, split evenly between the spirals
+/// * `noise_stddev` - Standard deviation of normal distribution noise added to each point
+/// * `rng` - Random number generator used to create the noise
+pub fn make_spirals(
+    n_samples: usize,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 2], [f64; 1])> {
+    let n_first = n_samples / 2;
+    let spiral = |n: usize, target: f64| {
+        (0..n).map(move |s| {
+            let t = 3. * PI * s as f64 / n as f64;
+            let r = t / (3. * PI);
+            ([target * r * t.cos(), target * r * t.sin()], target)
+        })
+    };
+    finish(
+        spiral(n_first, -1.).chain(spiral(n_samples - n_first, 1.)),
+        noise_stddev,
+        rng,
+    )
+}
+
+/// Points drawn uniformly from `[-1, 1]` in each dimension, with a target of 1
+/// when both coordinates have the same sign and -1 otherwise
+///
+/// The noise is added after the targets are assigned, blurring the boundary.
+pub fn make_xor(
+    n_samples: usize,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 2], [f64; 1])> {
+    let points: Vec<_> = (0..n_samples)
+        .map(|_| {
+            let x = [rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)];
+            (x, if x[0] * x[1] > 0. { 1. } else { -1. })
+        })
+        .collect();
+    finish(points.into_iter(), noise_stddev, rng)
+}
+
+/// Inputs drawn uniformly from `[-1, 1]`, with targets `weights . x + bias` plus noise
+pub fn make_linear<const I: usize>(
+    n_samples: usize,
+    weights: [f64; I],
+    bias: f64,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; I], [f64; 1])> {
+    let noise = noise(noise_stddev);
+    (0..n_samples)
+        .map(|_| {
+            let x: [f64; I] = std::array::from_fn(|_| rng.gen_range(-1.0..1.0));
+            let y = x.iter().zip(weights).map(|(x, w)| x * w).sum::<f64>() + bias;
+            (x, [y + noise.sample(rng)])
+        })
+        .collect()
+}
+
+/// Inputs drawn uniformly from `[-pi, pi]`, with targets `sin(x)` plus noise
+pub fn make_sine(
+    n_samples: usize,
+    noise_stddev: f64,
+    rng: &mut impl Rng,
+) -> Vec<([f64; 1], [f64; 1])> {
+    let noise = noise(noise_stddev);
+    (0..n_samples)
+        .map(|_| {
+            let x = rng.gen_range(-PI..PI);
+            ([x], [x.sin() + noise.sample(rng)])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        make_blobs, make_circles, make_linear, make_moons, make_sine, make_spirals, make_xor,
+    };
+    use approx::assert_abs_diff_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rstest::rstest;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(42)
+    }
+
+    #[rstest]
+    #[case::moons(make_moons(101, 0.1, &mut rng()))]
+    #[case::circles(make_circles(101, 0.5, 0.1, &mut rng()))]
+    #[case::spirals(make_spirals(101, 0.1, &mut rng()))]
+    fn test_balanced_classes(#[case] data: Vec<([f64; 2], [f64; 1])>) {
+        assert_eq!(data.len(), 101);
+        let positive = data.iter().filter(|(_, t)| t[0] == 1.).count();
+        let negative = data.iter().filter(|(_, t)| t[0] == -1.).count();
+        assert_eq!((positive, negative), (51, 50));
+    }
+
+    #[test]
+    fn test_seeded() {
+        assert_eq!(
+            make_moons(20, 0.1, &mut rng()),
+            make_moons(20, 0.1, &mut rng())
+        );
+        assert_ne!(
+            make_moons(20, 0.1, &mut rng()),
+            make_moons(20, 0.1, &mut StdRng::seed_from_u64(0))
+        );
+    }
+
+    #[test]
+    fn test_noiseless_geometry() {
+        for ([x, y], [t]) in make_circles(40, 0.3, 0., &mut rng()) {
+            let radius = if t > 0. { 0.3 } else { 1.0 };
+            assert_abs_diff_eq!(x.hypot(y), radius, epsilon = 1e-12);
+        }
+        for ([x, y], [t]) in make_xor(40, 0., &mut rng()) {
+            assert_eq!(t > 0., x * y > 0.);
+        }
+        for ([x], [y]) in make_sine(40, 0., &mut rng()) {
+            assert_eq!(y, x.sin());
+        }
+        for ([a, b], [y]) in make_linear(40, [2.0, -1.0], 0.5, 0., &mut rng()) {
+            assert_abs_diff_eq!(y, 2.0 * a - b + 0.5, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_blobs() {
+        let data = make_blobs::<3>(30, 0.5, &mut rng());
+        assert_eq!(data.len(), 30);
+        for c in 0..3 {
+            assert_eq!(data.iter().filter(|(_, t)| t[c] == 1.).count(), 10);
+        }
+        assert!(data.iter().all(|(_, t)| t.iter().sum::<f64>() == 1.));
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::value::{Operation, Value};
+
+// Binding strength of rendered expressions, higher binds tighter
+const SUM: u8 = 1;
+const PRODUCT: u8 = 2;
+const UNARY: u8 = 3;
+const POWER: u8 = 4;
+const ATOM: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notation {
+    Plain,
+    Latex,
+}
+
+/// A rendered sub-expression along with how tightly it binds
+struct Rendered {
+    text: String,
+    precedence: u8,
+}
+
+impl Rendered {
+    fn new(text: String, precedence: u8) -> Self {
+        Self { text, precedence }
+    }
+
+    /// Text of the expression, wrapped in parentheses if it binds looser than `precedence`
+    fn wrap(self, precedence: u8, notation: Notation) -> String {
+        if self.precedence < precedence {
+            match notation {
+                Notation::Plain => format!("({})", self.text),
+                Notation::Latex => format!("\\left({}\\right)", self.text),
+            }
+        } else {
+            self.text
+        }
+    }
+}
+
+/// Format a number compactly for display
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{value:.0}");
+    }
+    let text = format!("{value:.4}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "0" || text == "-0" {
+        format!("{value:e}")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Format a label as LaTeX, using subscripts for trailing digits (e.g. `w1` as `w_{1}`)
+fn latex_label(label: &str) -> String {
+    let name = label.trim_end_matches(|c: char| c.is_ascii_digit());
+    let index = &label[name.len()..];
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic()) {
+        let name = if name.len() == 1 {
+            name.to_string()
+        } else {
+            format!("\\mathrm{{{name}}}")
+        };
+        if index.is_empty() {
+            name
+        } else {
+            format!("{name}_{{{index}}}")
+        }
+    } else {
+        format!("\\mathrm{{{}}}", label.replace('_', "\\_"))
+    }
+}
+
+/// Nodes that depend on at least one labelled Value
+fn symbolic_nodes(v: &Value) -> HashSet<Value> {
+    let mut symbolic = HashSet::new();
+    for node in v.topological_order() {
+        if node.borrow().label.is_some() || node.children().iter().any(|c| symbolic.contains(c)) {
+            symbolic.insert(node);
+        }
+    }
+    symbolic
+}
+
+fn sum(terms: Vec<Rendered>, notation: Notation) -> Rendered {
+    if terms.is_empty() {
+        return Rendered::new("0".to_string(), ATOM);
+    }
+    Rendered::new(
+        terms
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| {
+                // Avoid juxtaposing a negative term with the addition sign
+                if i > 0 && c.precedence == UNARY {
+                    c.wrap(POWER, notation)
+                } else {
+                    c.wrap(SUM, notation)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" + "),
+        SUM,
+    )
+}
+
+fn product(factors: Vec<Rendered>, notation: Notation) -> Rendered {
+    Rendered::new(
+        factors
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| {
+                // Avoid juxtaposing a negative factor with the multiplication sign
+                c.wrap(if i == 0 { PRODUCT } else { POWER }, notation)
+            })
+            .collect::<Vec<String>>()
+            .join(match notation {
+                Notation::Plain => " * ",
+                Notation::Latex => " \\cdot ",
+            }),
+        PRODUCT,
+    )
+}
+
+fn render(v: &Value, root: bool, symbolic: &HashSet<Value>, notation: Notation) -> Rendered {
+    let label = v.borrow().label.clone();
+    match (label, v.operation()) {
+        (Some(label), operation) if !root || operation.is_none() => {
+            return Rendered::new(
+                match notation {
+                    Notation::Plain => label,
+                    Notation::Latex => latex_label(&label),
+                },
+                ATOM,
+            )
+        }
+        _ => (),
+    }
+    if !symbolic.contains(v) {
+        let data = v.data();
+        return Rendered::new(format_number(data), if data < 0. { UNARY } else { ATOM });
+    }
+    let children: Vec<Rendered> = v
+        .children()
+        .iter()
+        .map(|c| render(c, false, symbolic, notation))
+        .collect();
+    let function = |plain: &str, latex: &str, children: Vec<Rendered>| {
+        let argument = children.into_iter().next().unwrap().text;
+        match notation {
+            Notation::Plain => Rendered::new(format!("{plain}({argument})"), ATOM),
+            Notation::Latex => Rendered::new(format!("{latex}\\left({argument}\\right)"), ATOM),
+        }
+    };
+    match v.operation() {
+        Some(Operation::Add) | Some(Operation::Sum) => sum(children, notation),
+        Some(Operation::Multiply) => product(children, notation),
+        Some(Operation::Subtract) => {
+            let mut children = children.into_iter();
+            let left = children.next().unwrap().wrap(SUM, notation);
+            let right = children.next().unwrap();
+            let right = if right.precedence == UNARY {
+                right.wrap(POWER, notation)
+            } else {
+                right.wrap(PRODUCT, notation)
+            };
+            Rendered::new(format!("{left} - {right}"), SUM)
+        }
+        Some(Operation::Negate) => Rendered::new(
+            format!(
+                "-{}",
+                children.into_iter().next().unwrap().wrap(POWER, notation)
+            ),
+            UNARY,
+        ),
+        Some(Operation::Divide) => {
+            let mut children = children.into_iter();
+            let numerator = children.next().unwrap();
+            let denominator = children.next().unwrap();
+            match notation {
+                Notation::Plain => Rendered::new(
+                    format!(
+                        "{} / {}",
+                        numerator.wrap(PRODUCT, notation),
+                        denominator.wrap(POWER, notation)
+                    ),
+                    PRODUCT,
+                ),
+                Notation::Latex => Rendered::new(
+                    format!("\\frac{{{}}}{{{}}}", numerator.text, denominator.text),
+                    ATOM,
+                ),
+            }
+        }
+        Some(Operation::Dot) => {
+            let mut children = children;
+            let b = children.split_off(children.len() / 2);
+            sum(
+                children
+                    .into_iter()
+                    .zip(b)
+                    .map(|(a, b)| product(vec![a, b], notation))
+                    .collect(),
+                notation,
+            )
+        }
+        Some(Operation::Pow) => {
+            let mut children = children.into_iter();
+            let base = children.next().unwrap().wrap(ATOM, notation);
+            let exponent = children.next().unwrap();
+            match notation {
+                Notation::Plain => {
+                    Rendered::new(format!("{base}^{}", exponent.wrap(POWER, notation)), POWER)
+                }
+                Notation::Latex => Rendered::new(format!("{base}^{{{}}}", exponent.text), POWER),
+            }
+        }
+        Some(Operation::Exponent) => match notation {
+            Notation::Plain => function("exp", "\\exp", children),
+            Notation::Latex => Rendered::new(
+                format!("e^{{{}}}", children.into_iter().next().unwrap().text),
+                POWER,
+            ),
+        },
+        Some(Operation::Log) => function("ln", "\\ln", children),
+        Some(Operation::Abs) => match notation {
+            Notation::Plain => function("abs", "\\operatorname{abs}", children),
+            Notation::Latex => Rendered::new(
+                format!(
+                    "\\left|{}\\right|",
+                    children.into_iter().next().unwrap().text
+                ),
+                ATOM,
+            ),
+        },
+        Some(Operation::Tanh) => function("tanh", "\\tanh", children),
+        Some(Operation::Relu) => function("relu", "\\operatorname{ReLU}", children),
+        None => unreachable!("Leaves are either labelled or constant"),
+    }
+}
+
+/// Number of parents of each node within the DAG leading to `v`
+fn parent_counts(v: &Value) -> HashMap<Value, usize> {
+    let mut counts = HashMap::new();
+    for node in v.topological_order() {
+        for child in node.children() {
+            *counts.entry(child).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Shared operation nodes are expanded once and given a number, with later
+/// occurrences printing a back-reference instead of the whole sub-DAG
+struct TreeRenderer {
+    parents: HashMap<Value, usize>,
+    rendered: HashMap<Value, usize>,
+    output: String,
+}
+
+impl TreeRenderer {
+    fn render(&mut self, v: &Value, prefix: &str, last: bool, root: bool) {
+        let mut name = match (v.borrow().label.clone(), v.operation()) {
+            (Some(label), Some(op)) => format!("{label} = {}", String::from(op)),
+            (Some(label), None) => label,
+            (None, Some(op)) => op.into(),
+            (None, None) => "value".to_string(),
+        };
+        let (branch, indent) = match (root, last) {
+            (true, _) => ("", ""),
+            (false, true) => ("└── ", "    "),
+            (false, false) => ("├── ", "│   "),
+        };
+        let children = v.children();
+        if !children.is_empty() && self.parents.get(v).is_some_and(|n| *n > 1) {
+            if let Some(id) = self.rendered.get(v) {
+                self.output
+                    .push_str(&format!("{prefix}{branch}{name} [see #{id}]\n"));
+                return;
+            }
+            let id = self.rendered.len() + 1;
+            self.rendered.insert(v.clone(), id);
+            name = format!("{name} #{id}");
+        }
+        self.output.push_str(&format!(
+            "{prefix}{branch}{name} [data={:.4}, grad={:.4}]\n",
+            v.data(),
+            v.gradient()
+        ));
+        let prefix = format!("{prefix}{indent}");
+        for (i, child) in children.iter().enumerate() {
+            self.render(child, &prefix, i + 1 == children.len(), false);
+        }
+    }
+}
+
+impl Value {
+    /// Render the expression that produced this Value as infix math, e.g. `tanh(w1 * x1 + b)`
+    ///
+    /// Nodes with a label are shown by name, while sub-expressions that do
+    /// not depend on any labelled Value are collapsed into their numeric value
+    pub fn formula(&self) -> String {
+        render(self, true, &symbolic_nodes(self), Notation::Plain).text
+    }
+
+    /// Render the expression that produced this Value as LaTeX math
+    ///
+    /// Follows the same rules as `formula`
+    pub fn latex(&self) -> String {
+        render(self, true, &symbolic_nodes(self), Notation::Latex).text
+    }
+
+    /// Render the DAG leading to this Value as an indented tree, showing the
+    /// data and gradient of every node
+    ///
+    /// Operation nodes with several parents are expanded the first time they
+    /// appear and numbered, e.g. `+ #1`, with later occurrences shown as
+    /// `+ [see #1]`
+    pub fn tree(&self) -> String {
+        let mut renderer = TreeRenderer {
+            parents: parent_counts(self),
+            rendered: HashMap::new(),
+            output: String::new(),
+        };
+        renderer.render(self, "", true, true);
+        renderer.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::Value;
+
+    #[test]
+    fn test_formula_neuron() {
+        let x1 = Value::named("x1", 2.0);
+        let w1 = Value::named("w1", -3.0);
+        let x2 = Value::named("x2", 0.0);
+        let w2 = Value::named("w2", 1.0);
+        let b = Value::named("b", 6.88);
+        let o = (w1 * x1 + w2 * x2 + b).tanh();
+        assert_eq!(o.formula(), "tanh(w1 * x1 + w2 * x2 + b)");
+        assert_eq!(
+            o.latex(),
+            "\\tanh\\left(w_{1} \\cdot x_{1} + w_{2} \\cdot x_{2} + b\\right)"
+        );
+    }
+
+    #[test]
+    fn test_formula_precedence() {
+        let a = Value::named("a", 2.0);
+        let b = Value::named("b", 3.0);
+        let c = Value::named("c", 4.0);
+        let v = (a.clone() + b.clone()) * c.clone();
+        assert_eq!(v.formula(), "(a + b) * c");
+        let v = (a.clone() * b.clone()).powf(c.clone() + 1.0);
+        assert_eq!(v.formula(), "(a * b)^(c + 1)");
+        assert_eq!(v.latex(), "\\left(a \\cdot b\\right)^{c + 1}");
+        let v = a.clone().exp() * -2.0;
+        assert_eq!(v.formula(), "exp(a) * (-2)");
+        assert_eq!(v.latex(), "e^{a} \\cdot \\left(-2\\right)");
+    }
+
+    #[test]
+    fn test_formula_collapses_constants() {
+        let x = Value::named("x", 1.5);
+        let v = x * (Value::from(2.0) * Value::from(0.25) + 1.0).relu();
+        assert_eq!(v.formula(), "x * 1.5");
+
+        let v = Value::from(3.0) * Value::from(4.0);
+        assert_eq!(v.formula(), "12");
+    }
+
+    #[test]
+    fn test_formula_labelled_intermediate() {
+        let x = Value::named("x", 1.0);
+        let h = (x * 2.0).relu();
+        h.set_label("h");
+        assert_eq!(h.formula(), "relu(x * 2)");
+        assert_eq!(h.clone().tanh().formula(), "tanh(h)");
+    }
+
+    #[test]
+    fn test_formula_sub_neg_div() {
+        let a = Value::named("a", 2.0);
+        let b = Value::named("b", 3.0);
+        let c = Value::named("c", 4.0);
+        let v = (a.clone() - (b.clone() - c.clone())) / -(b.clone() * c.clone());
+        assert_eq!(v.formula(), "(a - (b - c)) / (-(b * c))");
+        assert_eq!(
+            v.latex(),
+            "\\frac{a - \\left(b - c\\right)}{-\\left(b \\cdot c\\right)}"
+        );
+        let v = a.clone() - b.clone() * c.clone() - -a.clone();
+        assert_eq!(v.formula(), "a - b * c - (-a)");
+        assert_eq!((a + -b).formula(), "a + (-b)");
+    }
+
+    #[test]
+    fn test_formula_sum_and_dot() {
+        let w: Vec<Value> = (0..3)
+            .map(|i| Value::named(&format!("w{i}"), 1.0))
+            .collect();
+        let x: Vec<Value> = (0..3)
+            .map(|i| Value::named(&format!("x{i}"), 1.0))
+            .collect();
+        let v = Value::dot(&w, &x) + Value::named("b", 0.0);
+        assert_eq!(v.formula(), "w0 * x0 + w1 * x1 + w2 * x2 + b");
+        let v: Value = x.into_iter().sum::<Value>() * 2.0;
+        assert_eq!(v.formula(), "(x0 + x1 + x2) * 2");
+    }
+
+    #[test]
+    fn test_tree() {
+        let x = Value::named("x", 1.0);
+        let y = (x * 2.0).relu();
+        y.backward();
+        assert_eq!(
+            y.tree(),
+            "ReLU [data=2.0000, grad=1.0000]\n\
+             └── * [data=2.0000, grad=1.0000]\n    \
+                 ├── x [data=1.0000, grad=2.0000]\n    \
+                 └── value [data=2.0000, grad=1.0000]\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_shared() {
+        let x = Value::named("x", 1.0);
+        let h = (x.clone() + 1.0).with_label("h");
+        let y = h.clone() * h.tanh();
+        assert_eq!(
+            y.tree(),
+            "* [data=1.9281, grad=0.0000]\n\
+             ├── h = + #1 [data=2.0000, grad=0.0000]\n\
+             │   ├── x [data=1.0000, grad=0.0000]\n\
+             │   └── value [data=1.0000, grad=0.0000]\n\
+             └── tanh [data=0.9640, grad=0.0000]\n    \
+                 └── h = + [see #1]\n"
+        );
+    }
+}
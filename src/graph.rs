@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::value::{Operation, Value};
+
+/// Serializable description of a single node in a DAG
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphNode {
+    #[serde(with = "non_finite")]
+    pub data: f64,
+    #[serde(with = "non_finite")]
+    pub gradient: f64,
+    pub label: Option<String>,
+    pub operation: Option<Operation>,
+    /// Indices of the children within the `Graph` nodes
+    pub children: Vec<usize>,
+}
+
+/// JSON has no representation of NaN or infinity, so these are stored as strings
+mod non_finite {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_str(&value.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(f64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(n) => Ok(n),
+            Repr::Text(t) => t.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Flat, serializable description of the DAG leading to a Value
+///
+/// Nodes are ordered so that every node comes after all of its children, with
+/// the Value the graph was created from last. Nodes shared by multiple parents
+/// are stored once and referenced by index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+}
+
+/// Error raised when saving or restoring a Graph
+#[derive(Debug)]
+pub enum GraphError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The graph has no nodes
+    Empty,
+    /// A node references a child that does not precede it
+    InvalidChild {
+        node: usize,
+        child: usize,
+    },
+    /// A node has the wrong number of children for its operation
+    InvalidArity {
+        node: usize,
+        operation: Option<Operation>,
+        children: usize,
+    },
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::Io(e) => write!(f, "{e}"),
+            GraphError::Json(e) => write!(f, "{e}"),
+            GraphError::Empty => write!(f, "graph contains no nodes"),
+            GraphError::InvalidChild { node, child } => {
+                write!(f, "node {node} references invalid child {child}")
+            }
+            GraphError::InvalidArity {
+                node,
+                operation,
+                children,
+            } => {
+                let operation = operation.map_or("leaf".to_string(), String::from);
+                write!(
+                    f,
+                    "node {node} has {children} children, invalid for {operation}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl From<std::io::Error> for GraphError {
+    fn from(value: std::io::Error) -> Self {
+        GraphError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for GraphError {
+    fn from(value: serde_json::Error) -> Self {
+        GraphError::Json(value)
+    }
+}
+
+impl From<&Value> for Graph {
+    fn from(value: &Value) -> Self {
+        let topo = value.topological_order();
+        let index: HashMap<Value, usize> = topo
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v.clone(), i))
+            .collect();
+        Graph {
+            nodes: topo
+                .iter()
+                .map(|v| {
+                    let internal = v.borrow();
+                    GraphNode {
+                        data: internal.data,
+                        gradient: internal.gradient,
+                        label: internal.label.clone(),
+                        operation: internal.operation,
+                        children: internal.children.iter().map(|c| index[c]).collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether an operation can be applied to the given number of children
+fn valid_arity(operation: Option<Operation>, children: usize) -> bool {
+    match operation {
+        None => children == 0,
+        Some(
+            Operation::Negate
+            | Operation::Tanh
+            | Operation::Exponent
+            | Operation::Log
+            | Operation::Abs
+            | Operation::Relu,
+        ) => children == 1,
+        Some(
+            Operation::Add
+            | Operation::Subtract
+            | Operation::Multiply
+            | Operation::Divide
+            | Operation::Pow,
+        ) => children == 2,
+        Some(Operation::Dot) => children % 2 == 0,
+        Some(Operation::Sum) => true,
+    }
+}
+
+impl Graph {
+    /// Rebuild the DAG, returning the Value the graph was created from
+    pub fn to_value(&self) -> Result<Value, GraphError> {
+        let mut values: Vec<Value> = Vec::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !valid_arity(node.operation, node.children.len()) {
+                return Err(GraphError::InvalidArity {
+                    node: i,
+                    operation: node.operation,
+                    children: node.children.len(),
+                });
+            }
+            let children = node
+                .children
+                .iter()
+                .map(|&child| {
+                    values
+                        .get(child)
+                        .cloned()
+                        .ok_or(GraphError::InvalidChild { node: i, child })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let v = Value::from_parts(node.data, node.operation, children, node.label.clone());
+            v.borrow_mut().gradient = node.gradient;
+            values.push(v);
+        }
+        values.pop().ok_or(GraphError::Empty)
+    }
+
+    /// Write the graph to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), GraphError> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    /// Read a graph from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GraphError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+}
+
+impl Value {
+    /// Serialize the DAG leading to this Value as JSON
+    pub fn to_json(&self) -> Result<String, GraphError> {
+        Ok(serde_json::to_string(&Graph::from(self))?)
+    }
+
+    /// Rebuild a DAG from JSON created by `to_json`
+    pub fn from_json(json: &str) -> Result<Value, GraphError> {
+        serde_json::from_str::<Graph>(json)?.to_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Graph, GraphError};
+    use crate::value::Value;
+
+    fn example() -> Value {
+        let a = Value::named("a", 2.0);
+        let b = Value::from(-3.0);
+        let shared = a.clone() * b;
+        let out = (shared.clone() + shared.exp()).tanh();
+        out.backward();
+        out
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let out = example();
+        let restored = Value::from_json(&out.to_json().unwrap()).unwrap();
+        assert_eq!(Graph::from(&restored), Graph::from(&out));
+    }
+
+    #[test]
+    fn test_shared_nodes_preserved() {
+        let out = example();
+        let restored = Value::from_json(&out.to_json().unwrap()).unwrap();
+        let sum = &restored.children()[0];
+        let shared = &sum.children()[0];
+        assert!(std::rc::Rc::ptr_eq(
+            shared,
+            &sum.children()[1].children()[0]
+        ));
+        assert_eq!(Graph::from(&restored).nodes.len(), 6);
+    }
+
+    #[test]
+    fn test_equal_nodes_kept_distinct() {
+        let out: Value = (0..2000).map(|_| Value::from(1.0)).sum();
+        let restored = Value::from_json(&out.to_json().unwrap()).unwrap();
+        let children = restored.children();
+        assert_eq!(children.len(), 2000);
+        for (i, child) in children.iter().enumerate().skip(1) {
+            assert!(!std::rc::Rc::ptr_eq(child, &children[i - 1]));
+        }
+        assert_eq!(Graph::from(&restored).nodes.len(), 2001);
+    }
+
+    #[test]
+    fn test_restored_graph_is_live() {
+        let out = example();
+        let restored = Value::from_json(&out.to_json().unwrap()).unwrap();
+        let graph = Graph::from(&restored);
+        restored.find("a").unwrap().set_data(1.0);
+        restored.recompute();
+        assert_ne!(restored.data(), graph.nodes.last().unwrap().data);
+    }
+
+    #[test]
+    fn test_restored_graph_recompute_dirty() {
+        let restored = Value::from_json(&example().to_json().unwrap()).unwrap();
+        restored.find("a").unwrap().set_data(1.0);
+        restored.recompute_dirty();
+        let expected = example();
+        expected.find("a").unwrap().set_data(1.0);
+        expected.recompute();
+        assert_eq!(restored.data(), expected.data());
+    }
+
+    #[test]
+    fn test_non_finite_data() {
+        let v = Value::from(f64::NAN) + Value::from(f64::NEG_INFINITY);
+        let restored = Value::from_json(&v.to_json().unwrap()).unwrap();
+        assert!(restored.data().is_nan());
+        assert_eq!(restored.children()[1].data(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_invalid_graph() {
+        let json = r#"{"nodes": [{"data": 1.0, "gradient": 0.0, "label": null,
+            "operation": "Relu", "children": [0]}]}"#;
+        assert!(matches!(
+            Value::from_json(json),
+            Err(GraphError::InvalidChild { node: 0, child: 0 })
+        ));
+        assert!(matches!(
+            Value::from_json(r#"{"nodes": []}"#),
+            Err(GraphError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_arity() {
+        let node = |operation: &str, children: &str| {
+            format!(
+                r#"{{"data": 1.0, "gradient": 0.0, "label": null,
+                "operation": {operation}, "children": {children}}}"#
+            )
+        };
+        let leaf = node("null", "[]");
+        let cases = [
+            (node(r#""Tanh""#, "[]"), 0),
+            (node(r#""Relu""#, "[0, 0]"), 2),
+            (node(r#""Multiply""#, "[0]"), 1),
+            (node(r#""Divide""#, "[0, 0, 0]"), 3),
+            (node(r#""Dot""#, "[0, 0, 0]"), 3),
+            (node("null", "[0]"), 1),
+        ];
+        for (invalid, count) in cases {
+            let json = format!(r#"{{"nodes": [{leaf}, {invalid}]}}"#);
+            assert!(matches!(
+                Value::from_json(&json),
+                Err(GraphError::InvalidArity { node: 1, children, .. }) if children == count
+            ));
+        }
+        let json = format!(r#"{{"nodes": [{leaf}, {}]}}"#, node(r#""Tanh""#, "[]"));
+        assert_eq!(
+            Value::from_json(&json).unwrap_err().to_string(),
+            "node 1 has 0 children, invalid for tanh"
+        );
+        let json = format!(r#"{{"nodes": [{leaf}, {}]}}"#, node(r#""Sum""#, "[]"));
+        assert!(Value::from_json(&json).is_ok());
+    }
+}
@@ -1,44 +1,61 @@
+pub mod anomaly;
+pub mod codegen;
+pub mod data;
+pub mod datasets;
+pub mod format;
+#[cfg(feature = "serde")]
+pub mod graph;
 pub mod nn;
+pub mod npy;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod optim;
+pub mod parse;
+#[cfg(feature = "serde")]
+pub mod safetensors;
+pub mod train;
 pub mod value;
 
 #[cfg(feature = "draw_graph")]
 pub mod draw_dot {
     use crate::value::Value;
-    use petgraph::dot::{Config, Dot};
+    use petgraph::dot::Dot;
     use petgraph::graph::DiGraph;
-    use std::collections::{HashMap, HashSet};
+    use std::collections::HashMap;
     use std::fs::File;
     use std::io;
     use std::io::Write;
 
-    /// Build a set of Node and Edges for the Graph
-    fn trace_graph(v: Value) -> (HashSet<Value>, HashSet<(Value, Value)>) {
-        let mut nodes = HashSet::new();
-        let mut edges = HashSet::new();
-
-        fn build_graph(v: Value, nodes: &mut HashSet<Value>, edges: &mut HashSet<(Value, Value)>) {
-            if !nodes.contains(&v) {
-                nodes.insert(v.clone());
-                for child in v.children() {
-                    edges.insert((child.clone(), v.clone()));
-                    build_graph(child, nodes, edges)
-                }
-            }
-        }
-        build_graph(v, &mut nodes, &mut edges);
+    /// Build the Nodes and Edges for the Graph
+    ///
+    /// Each edge joins a child to its parent along with the position of the
+    /// child among the operands, keeping repeated operands such as `a - a`
+    fn trace_graph(v: Value) -> (Vec<Value>, Vec<(Value, Value, usize)>) {
+        let nodes = v.topological_order();
+        let edges = nodes
+            .iter()
+            .flat_map(|parent| {
+                parent
+                    .children()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, child)| (child, parent.clone(), i))
+            })
+            .collect();
         (nodes, edges)
     }
 
     /// Create a DiGraph based on DAG leading to a Value
-    fn create_graph(v: Value) -> DiGraph<String, ()> {
+    fn create_graph(v: Value) -> DiGraph<String, String> {
         let (nodes, edges) = trace_graph(v);
         let mut g = DiGraph::new();
         let mut op_graph = HashMap::new();
         let mut node_graph = HashMap::new();
 
         for n in nodes {
+            let label = n.label().map_or(String::new(), |l| format!("{l} | "));
             let idx = g.add_node(format!(
-                "{{ data {:.4} | grad {:.4} }}",
+                "{{ {label}data {:.4} | grad {:.4} }}",
                 n.data(),
                 n.gradient()
             ));
@@ -46,13 +63,19 @@ pub mod draw_dot {
             // A node that is the result of an operation has a separate bubble to connect to
             if let Some(op) = n.operation() {
                 let op_idx = g.add_node(op.into());
-                g.add_edge(op_idx, idx, ());
+                g.add_edge(op_idx, idx, String::new());
                 op_graph.insert(n, op_idx);
             }
         }
-        for (child, parent) in edges {
-            let idx = *op_graph.get(&parent).unwrap();
-            g.add_edge(*node_graph.get(&child).unwrap(), idx, ());
+        for (child, parent, position) in edges {
+            // Operand positions only matter when there is more than one
+            let label = if parent.children().len() > 1 {
+                position.to_string()
+            } else {
+                String::new()
+            };
+            let idx = op_graph[&parent];
+            g.add_edge(node_graph[&child], idx, label);
         }
         g
     }
@@ -60,7 +83,7 @@ pub mod draw_dot {
     /// Create a dot file description of the DAG that leads to Value
     pub fn draw_dot(v: Value, filename: &str) -> Result<(), io::Error> {
         let g = create_graph(v);
-        let mut dot = format!("{:?}", Dot::with_config(&g, &[Config::EdgeNoLabel]));
+        let mut dot = format!("{:?}", Dot::new(&g));
         // Hack output dot file for options not availabel in petgraph
         dot = dot.replace("\\\"", "");
         dot.insert_str(10, "    rankdir=\"LR\"");
@@ -68,6 +91,30 @@ pub mod draw_dot {
         let mut f = File::create(filename)?;
         f.write_all(dot.as_bytes())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::draw_dot;
+        use crate::value::Value;
+
+        #[test]
+        fn test_repeated_operands() {
+            let a = Value::from(3.0);
+            let y = (a.clone() - a.clone()) + a.clone() / a;
+            let path = std::env::temp_dir().join(format!("ugradrs-{}.dot", std::process::id()));
+            draw_dot(y, path.to_str().unwrap()).unwrap();
+            let dot = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let operands = |position: &str| {
+                dot.lines()
+                    .filter(|l| l.contains("->") && l.contains(&format!("label = \"{position}\"")))
+                    .count()
+            };
+            // Both operands of -, / and + are drawn
+            assert_eq!(operands("0"), 3);
+            assert_eq!(operands("1"), 3);
+        }
+    }
 }
 
 #[cfg(doctest)]
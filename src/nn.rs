@@ -1,43 +1,77 @@
-use rand::{thread_rng, Rng};
+pub mod activation;
+pub mod dynamic;
+pub mod functional;
+pub mod init;
+pub mod loss;
+pub mod regularization;
+pub mod state;
+pub mod utils;
+
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
 use std::ops::Add;
 
+use crate::nn::activation::Activation;
+use crate::nn::dynamic::{DynMlp, ShapeError};
+use crate::nn::init::{Initializer, Uniform};
+use crate::nn::regularization::{Constraint, Regularizer};
+use crate::nn::state::{Entry, StateDict, StateError};
 use crate::value::Value;
 
 #[derive(Debug)]
 pub struct Neuron<const N: usize> {
     weights: [Value; N],
     bias: Value,
-    linear: bool,
+    activation: Activation,
 }
 
 impl<const N: usize> Neuron<N> {
-    pub fn new(linear: bool) -> Self {
-        let mut rng = thread_rng();
+    pub fn new(activation: Activation) -> Self {
+        Self::new_with_rng(activation, &mut thread_rng())
+    }
+
+    /// Create a neuron with weights drawn uniformly from `[-1, 1)` by the
+    /// provided random number generator and a bias of zero
+    pub fn new_with_rng(activation: Activation, rng: &mut impl Rng) -> Self {
         Neuron {
             weights: (0..N)
-                .map(|_| Value::from(rng.gen_range(-1.0..1.0)))
+                .map(|i| Value::named(&format!("w{i}"), rng.gen_range(-1.0..1.0)))
                 .collect::<Vec<Value>>()
                 .try_into()
                 .unwrap(),
-            bias: Value::from(0.),
-            linear,
+            bias: Value::named("b", 0.),
+            activation,
         }
     }
 
     pub fn forward(&self, x: [Value; N]) -> Value {
-        let v = self
-            .weights
-            .clone()
-            .into_iter()
-            .zip(x.into_iter())
-            .map(|(a, b)| a * b)
-            .sum::<Value>()
-            .add(self.bias.clone());
-        if self.linear {
-            v
-        } else {
-            v.relu()
+        self.activation
+            .apply(Value::dot(&self.weights, &x).add(self.bias.clone()))
+    }
+
+    /// Redraw the weights and zero the bias, as in `new_with_rng`
+    pub fn reset_parameters(&self, rng: &mut dyn RngCore) {
+        for w in &self.weights {
+            w.set_data(rng.gen_range(-1.0..1.0))
         }
+        self.bias.set_data(0.);
+    }
+
+    /// Snapshot of the parameters, as `weight` with shape `[N]` and a scalar `bias`
+    pub fn state_dict(&self) -> StateDict {
+        StateDict::from_entries(&self.entries())
+    }
+
+    /// Copy the parameters from a snapshot created by `state_dict`
+    pub fn load_state_dict(&self, state: &StateDict) -> Result<(), StateError> {
+        state.load_entries(&self.entries())
+    }
+
+    fn entries(&self) -> Vec<Entry> {
+        vec![
+            Entry::new("weight", vec![N], self.weights.to_vec()),
+            Entry::new("bias", vec![], vec![self.bias.clone()]),
+        ]
     }
 
     fn parameters(&self) -> Vec<Value> {
@@ -47,33 +81,185 @@ impl<const N: usize> Neuron<N> {
     }
 }
 
+/// Prepend a scope to the label of each parameter, e.g. `w0` to `neuron1.w0`
+fn scope_labels(parameters: &[Value], scope: &str) {
+    for p in parameters {
+        let label = p
+            .label()
+            .map_or(scope.to_string(), |l| format!("{scope}.{l}"));
+        p.set_label(&label);
+    }
+}
+
+/// Combined penalty of a set of regularizers over the weights of a layer
+fn layer_penalty(regularizers: &[Box<dyn Regularizer>], weights: &[Vec<Value>]) -> Option<Value> {
+    if regularizers.is_empty() {
+        return None;
+    }
+    let weights = weights.concat();
+    Some(regularizers.iter().map(|r| r.penalty(&weights)).sum())
+}
+
+/// Apply each constraint to the weights of each neuron
+fn constrain(constraints: &[Box<dyn Constraint>], weights: &[Vec<Value>]) {
+    for w in weights {
+        for c in constraints {
+            c.apply(w)
+        }
+    }
+}
+
+/// Draw new weights for each neuron from an initializer and zero the biases
+fn initialize(
+    initializer: &dyn Initializer,
+    weights: &[Vec<Value>],
+    biases: &[Value],
+    inputs: usize,
+    rng: &mut dyn RngCore,
+) {
+    let data = initializer.weights(inputs, weights.len(), rng);
+    for (row, data) in weights.iter().zip(data) {
+        for (w, x) in row.iter().zip(data) {
+            w.set_data(x)
+        }
+    }
+    for b in biases {
+        b.set_data(0.)
+    }
+}
+
+fn layer_entries(layer: &(impl Layer + ?Sized)) -> Vec<Entry> {
+    vec![
+        Entry::new(
+            "weight",
+            vec![layer.outputs(), layer.inputs()],
+            layer.weights().concat(),
+        ),
+        Entry::new("bias", vec![layer.outputs()], layer.biases()),
+    ]
+}
+
 pub trait Layer {
+    /// Evaluate each neuron, which may panic if the number of inputs does not
+    /// match the layer
     fn forward(&self, x: Vec<Value>) -> Vec<Value>;
+
+    /// Evaluate each neuron, checking the number of inputs and outputs
+    fn try_forward(&self, x: &[Value]) -> Result<Vec<Value>, ShapeError> {
+        ShapeError::check(self.inputs(), x.len())?;
+        let y = self.forward(x.to_vec());
+        ShapeError::check(self.outputs(), y.len())?;
+        Ok(y)
+    }
+
     fn parameters(&self) -> Vec<Value>;
+
+    /// Number of inputs to each neuron
+    fn inputs(&self) -> usize;
+
+    /// Number of neurons
+    fn outputs(&self) -> usize;
+
+    fn activation(&self) -> Activation;
+
+    /// Incoming weights of each neuron, excluding biases
+    fn weights(&self) -> Vec<Vec<Value>>;
+
+    /// Bias of each neuron
+    fn biases(&self) -> Vec<Value>;
+
+    /// Snapshot of the parameters, as `weight` with shape `[outputs, inputs]`
+    /// and `bias` with shape `[outputs]`
+    fn state_dict(&self) -> StateDict {
+        StateDict::from_entries(&layer_entries(self))
+    }
+
+    /// Copy the parameters from a snapshot created by `state_dict`
+    fn load_state_dict(&self, state: &StateDict) -> Result<(), StateError> {
+        state.load_entries(&layer_entries(self))
+    }
+
+    /// Penalty from the regularizers attached to this layer, if any
+    fn penalty(&self) -> Option<Value> {
+        None
+    }
+
+    /// Enforce the constraints attached to this layer
+    fn apply_constraints(&self) {}
+
+    /// Re-initialize the parameters from a random number generator
+    fn reset_parameters(&self, rng: &mut dyn RngCore);
 }
 
 /// A Layer with the input and output dimensions as generics
 pub struct SizedLayer<const I: usize, const O: usize> {
     neurons: [Neuron<I>; O],
+    initializer: Box<dyn Initializer>,
+    regularizers: Vec<Box<dyn Regularizer>>,
+    constraints: Vec<Box<dyn Constraint>>,
 }
 
 impl<const I: usize, const O: usize> Default for SizedLayer<I, O> {
     fn default() -> Self {
-        Self::new(false)
+        Self::new(Activation::default())
     }
 }
 
 impl<const I: usize, const O: usize> SizedLayer<I, O> {
     /// Create a layer of the provided size, initialized with random weights
-    pub fn new(linear: bool) -> Self {
+    ///
+    /// Parameters are labelled by neuron, e.g. `neuron3.w0`
+    pub fn new(activation: Activation) -> Self {
+        Self::new_with_rng(activation, &mut thread_rng())
+    }
+
+    /// Create a layer with weights drawn from the provided random number generator
+    pub fn new_with_rng(activation: Activation, rng: &mut impl Rng) -> Self {
         Self {
             neurons: (0..O)
-                .map(|_| Neuron::new(linear))
+                .map(|i| {
+                    let n = Neuron::new_with_rng(activation, rng);
+                    scope_labels(&n.parameters(), &format!("neuron{i}"));
+                    n
+                })
                 .collect::<Vec<Neuron<I>>>()
                 .try_into()
                 .unwrap(),
+            initializer: Box::new(Uniform::default()),
+            regularizers: Vec::new(),
+            constraints: Vec::new(),
         }
     }
+
+    /// Re-initialize the weights with a different scheme, which is also used
+    /// by any later `reset_parameters`
+    pub fn with_initializer(self, initializer: impl Initializer + 'static) -> Self {
+        self.with_initializer_and_rng(initializer, &mut thread_rng())
+    }
+
+    /// Re-initialize the weights with a different scheme, drawing from the
+    /// provided random number generator
+    pub fn with_initializer_and_rng(
+        mut self,
+        initializer: impl Initializer + 'static,
+        rng: &mut dyn RngCore,
+    ) -> Self {
+        self.initializer = Box::new(initializer);
+        self.reset_parameters(rng);
+        self
+    }
+
+    /// Penalize the weights of this layer, excluding biases
+    pub fn with_regularizer(mut self, regularizer: impl Regularizer + 'static) -> Self {
+        self.regularizers.push(Box::new(regularizer));
+        self
+    }
+
+    /// Constrain the weights of each neuron in this layer
+    pub fn with_constraint(mut self, constraint: impl Constraint + 'static) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
 }
 
 impl<const I: usize, const O: usize> Layer for SizedLayer<I, O> {
@@ -87,17 +273,77 @@ impl<const I: usize, const O: usize> Layer for SizedLayer<I, O> {
     fn parameters(&self) -> Vec<Value> {
         self.neurons.iter().flat_map(|n| n.parameters()).collect()
     }
+
+    fn inputs(&self) -> usize {
+        I
+    }
+
+    fn outputs(&self) -> usize {
+        O
+    }
+
+    fn activation(&self) -> Activation {
+        self.neurons
+            .first()
+            .map_or(Activation::default(), |n| n.activation)
+    }
+
+    fn weights(&self) -> Vec<Vec<Value>> {
+        self.neurons.iter().map(|n| n.weights.to_vec()).collect()
+    }
+
+    fn biases(&self) -> Vec<Value> {
+        self.neurons.iter().map(|n| n.bias.clone()).collect()
+    }
+
+    fn penalty(&self) -> Option<Value> {
+        layer_penalty(&self.regularizers, &self.weights())
+    }
+
+    fn apply_constraints(&self) {
+        constrain(&self.constraints, &self.weights())
+    }
+
+    fn reset_parameters(&self, rng: &mut dyn RngCore) {
+        initialize(
+            self.initializer.as_ref(),
+            &self.weights(),
+            &self.biases(),
+            I,
+            rng,
+        )
+    }
 }
 
+/// A multi-layer perceptron with the input and output dimensions as generics
+///
+/// Layers are stored as a `DynMlp`, whose shape checks always pass as the
+/// sizes of consecutive layers are checked at compile time.
 pub struct Mlp<const I: usize, const O: usize> {
-    layers: Vec<Box<dyn Layer>>,
+    network: DynMlp,
 }
 
 impl<const I: usize, const O: usize> Mlp<I, O> {
+    /// Create a new Mlp with a single layer, to which further layers can be added
+    pub fn new(activation: Activation) -> Self {
+        Self::new_with_rng(activation, &mut thread_rng())
+    }
+
+    /// Create a new Mlp with a single layer whose weights are drawn from the
+    /// provided random number generator
+    ///
+    /// Passing the same generator to the layers added afterwards makes the
+    /// whole model reproducible from a single seed.
+    pub fn new_with_rng(activation: Activation, rng: &mut impl Rng) -> Self {
+        Self::from_layer(SizedLayer::new_with_rng(activation, rng))
+    }
+
     /// Create a new Mlp from an initial layer
+    ///
+    /// Parameters of each layer are labelled by position, e.g. `layer1.neuron3.w0`
     pub fn from_layer(layer: SizedLayer<I, O>) -> Mlp<I, O> {
         Self {
-            layers: vec![Box::new(layer)],
+            network: DynMlp::from_layer(layer),
         }
     }
 
@@ -105,53 +351,194 @@ impl<const I: usize, const O: usize> Mlp<I, O> {
     ///
     /// Consumes the current Mlp re-defining the type to have the same number
     /// of outputs as the added layer.
-    pub fn add_layer<const OUT: usize>(mut self, layer: SizedLayer<O, OUT>) -> Mlp<I, OUT> {
-        self.layers.push(Box::new(layer));
+    pub fn add_layer<const OUT: usize>(self, layer: SizedLayer<O, OUT>) -> Mlp<I, OUT> {
         Mlp {
-            layers: self.layers,
+            network: self
+                .network
+                .add_layer(layer)
+                .expect("Layer sizes are checked at compile time"),
         }
     }
 
     /// Create a prediction by evaluating an input through a forward pass of each layer
     pub fn forward(&self, x: [Value; I]) -> [Value; O] {
-        let mut x = x.to_vec();
-        for layer in &self.layers {
-            x = layer.forward(x)
-        }
-        x.try_into().unwrap()
+        self.network
+            .forward(&x)
+            .expect("Input size is checked at compile time")
+            .try_into()
+            .unwrap()
+    }
+
+    /// The layers of the Mlp, in order
+    pub fn layers(&self) -> &[Box<dyn Layer>] {
+        self.network.layers()
     }
 
     /// Complete list of parameters in the Mlp graph
     pub fn parameters(&self) -> Vec<Value> {
-        self.layers.iter().flat_map(|l| l.parameters()).collect()
+        self.network.parameters()
     }
 
     /// Set all parameter gradients back to zero
     pub fn zero_grad(&self) {
-        for p in self.parameters() {
-            p.zero_grad()
+        self.network.zero_grad()
+    }
+
+    /// Penalize the weights of every layer, excluding biases
+    pub fn with_regularizer(self, regularizer: impl Regularizer + 'static) -> Self {
+        Self {
+            network: self.network.with_regularizer(regularizer),
+        }
+    }
+
+    /// Constrain the weights of every neuron
+    pub fn with_constraint(self, constraint: impl Constraint + 'static) -> Self {
+        Self {
+            network: self.network.with_constraint(constraint),
         }
     }
+
+    /// Total penalty of the regularizers attached to the Mlp and its layers,
+    /// to be added to the loss
+    pub fn penalty(&self) -> Value {
+        self.network.penalty()
+    }
+
+    /// Enforce the constraints attached to the Mlp and its layers, typically
+    /// after each optimizer step
+    pub fn apply_constraints(&self) {
+        self.network.apply_constraints()
+    }
+
+    /// Re-initialize the parameters of every layer from a random number generator
+    pub fn reset_parameters(&self, rng: &mut dyn RngCore) {
+        self.network.reset_parameters(rng)
+    }
+
+    /// Re-initialize the parameters from a seed, so that the same seed always
+    /// gives the same model
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.reset_parameters(&mut StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Global L2 norm of the parameter gradients of each layer
+    pub fn layer_grad_norms(&self) -> Vec<f64> {
+        self.network.layer_grad_norms()
+    }
+
+    /// Snapshot of the parameters, with the entries of each layer scoped by
+    /// position, e.g. `layer0.weight`
+    pub fn state_dict(&self) -> StateDict {
+        self.network.state_dict()
+    }
+
+    /// Copy the parameters from a snapshot created by `state_dict`
+    pub fn load_state_dict(&self, state: &StateDict) -> Result<(), StateError> {
+        self.network.load_state_dict(state)
+    }
+}
+
+impl<const I: usize, const O: usize> From<Mlp<I, O>> for DynMlp {
+    fn from(value: Mlp<I, O>) -> Self {
+        value.network
+    }
+}
+
+impl<const I: usize, const O: usize> TryFrom<DynMlp> for Mlp<I, O> {
+    type Error = ShapeError;
+
+    /// Check the number of inputs and outputs of a DynMlp
+    fn try_from(value: DynMlp) -> Result<Self, Self::Error> {
+        ShapeError::check(I, value.inputs())?;
+        ShapeError::check(O, value.outputs())?;
+        Ok(Self { network: value })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::nn::{Layer, Mlp, SizedLayer};
-    use crate::value::Value;
+    use crate::nn::activation::Activation;
+    use crate::nn::init::{Constant, Initializer, XavierUniform};
+    use crate::nn::regularization::{MaxNorm, L1, L2};
+    use crate::nn::state::{StateDict, StateError, Tensor};
+    use crate::nn::{utils, Layer, Mlp, Neuron, SizedLayer};
+    use crate::optim::{Optimizer, Sgd};
+    use crate::value::{Operation, Value};
+    use approx::assert_abs_diff_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
     use rstest::{fixture, rstest};
 
     #[test]
     fn test_layer_forward() {
-        let l: SizedLayer<2, 3> = SizedLayer::new(false);
+        let l: SizedLayer<2, 3> = SizedLayer::new(Activation::Relu);
         let o = l.forward([2.0, 3.0].into_iter().map(Value::from).collect());
         assert_eq!(o.len(), 3);
     }
 
     #[fixture]
     fn mlp() -> Mlp<3, 1> {
-        Mlp::from_layer(SizedLayer::<3, 4>::new(false))
-            .add_layer(SizedLayer::<4, 4>::new(false))
-            .add_layer(SizedLayer::new(true))
+        Mlp::from_layer(SizedLayer::<3, 4>::new(Activation::Relu))
+            .add_layer(SizedLayer::<4, 4>::new(Activation::Relu))
+            .add_layer(SizedLayer::new(Activation::Identity))
+            .with_seed(7)
+    }
+
+    #[test]
+    fn test_layer_activation() {
+        let layer = SizedLayer::<2, 3>::new(Activation::Tanh);
+        let x = [Value::from(2.0), Value::from(-1.0)];
+        let o = layer.forward(x.to_vec());
+        assert!(o.iter().all(|o| o.operation() == Some(Operation::Tanh)));
+        let default = SizedLayer::<2, 3>::default().forward(x.to_vec());
+        assert!(default
+            .iter()
+            .all(|o| o.operation() == Some(Operation::Relu)));
+    }
+
+    fn data(parameters: &[Value]) -> Vec<f64> {
+        parameters.iter().map(|p| p.data()).collect()
+    }
+
+    #[test]
+    fn test_layer_initializer() {
+        let layer = SizedLayer::<3, 2>::new(Activation::Relu).with_initializer(Constant::new(0.5));
+        assert!(layer.weights().concat().iter().all(|w| w.data() == 0.5));
+
+        let layer = SizedLayer::<3, 2>::new(Activation::Relu)
+            .with_initializer_and_rng(XavierUniform::default(), &mut StdRng::seed_from_u64(1));
+        let expected = XavierUniform::default().weights(3, 2, &mut StdRng::seed_from_u64(1));
+        assert_eq!(data(&layer.weights().concat()), expected.concat());
+        // Later resets keep using the chosen initializer
+        layer.reset_parameters(&mut StdRng::seed_from_u64(1));
+        assert_eq!(data(&layer.weights().concat()), expected.concat());
+    }
+
+    #[test]
+    fn test_seeded_initialization() {
+        let a = Neuron::<3>::new_with_rng(Activation::Relu, &mut StdRng::seed_from_u64(1));
+        let b = Neuron::<3>::new_with_rng(Activation::Relu, &mut StdRng::seed_from_u64(1));
+        assert_eq!(data(&a.parameters()), data(&b.parameters()));
+
+        let layer =
+            SizedLayer::<3, 2>::new_with_rng(Activation::Relu, &mut StdRng::seed_from_u64(1));
+        let initial = data(&layer.parameters());
+        layer.reset_parameters(&mut StdRng::seed_from_u64(1));
+        assert_eq!(data(&layer.parameters()), initial);
+        layer.reset_parameters(&mut StdRng::seed_from_u64(2));
+        assert_ne!(data(&layer.parameters()), initial);
+
+        assert_eq!(data(&mlp().parameters()), data(&mlp().parameters()));
+
+        let seeded = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            Mlp::<3, 4>::new_with_rng(Activation::Relu, &mut rng).add_layer(
+                SizedLayer::<4, 1>::new_with_rng(Activation::Identity, &mut rng),
+            )
+        };
+        assert_eq!(data(&seeded(1).parameters()), data(&seeded(1).parameters()));
+        assert_ne!(data(&seeded(1).parameters()), data(&seeded(2).parameters()));
     }
 
     #[rstest]
@@ -160,6 +547,14 @@ mod tests {
         assert_eq!(o.len(), 1);
     }
 
+    #[test]
+    fn test_neuron_graph_size() {
+        let n: Neuron<3> = Neuron::new(Activation::Relu);
+        let o = n.forward([Value::from(2.0), Value::from(3.0), Value::from(-1.0)]);
+        // Inputs, weights and bias followed by dot product, bias addition and activation
+        assert_eq!(o.topological_order().len(), 10);
+    }
+
     #[rstest]
     fn test_mlp_parameters(mlp: Mlp<3, 1>) {
         let p = mlp.parameters();
@@ -167,7 +562,82 @@ mod tests {
     }
 
     #[rstest]
-    fn test_mpl_train(mlp: Mlp<3, 1>) {
+    fn test_mlp_parameter_labels(mlp: Mlp<3, 1>) {
+        let p = mlp.parameters();
+        assert_eq!(p[0].label().unwrap(), "layer0.neuron0.w0");
+        assert_eq!(p[3].label().unwrap(), "layer0.neuron0.b");
+        assert_eq!(p[9].label().unwrap(), "layer0.neuron2.w1");
+        assert_eq!(p[40].label().unwrap(), "layer2.neuron0.b");
+
+        let o = mlp.forward([Value::from(2.0), Value::from(3.0), Value::from(-1.0)]);
+        assert!(o[0].find("layer1.neuron3.w2").is_some());
+    }
+
+    #[rstest]
+    fn test_layer_grad_norms(mlp: Mlp<3, 1>) {
+        let o = mlp.forward([Value::from(2.0), Value::from(3.0), Value::from(-1.0)]);
+        o[0].backward();
+        let norms = mlp.layer_grad_norms();
+        assert_eq!(norms.len(), 3);
+        let total = norms.iter().map(|n| n * n).sum::<f64>().sqrt();
+        assert_abs_diff_eq!(total, utils::grad_norm(&mlp.parameters()), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_regularization() {
+        let mlp: Mlp<2, 1> = Mlp::from_layer(
+            SizedLayer::<2, 3>::new(Activation::Relu).with_regularizer(L1::new(0.5)),
+        )
+        .add_layer(SizedLayer::new(Activation::Identity))
+        .with_regularizer(L2::new(0.1));
+        let weights: Vec<Value> = mlp
+            .layers()
+            .iter()
+            .flat_map(|l| l.weights())
+            .flatten()
+            .collect();
+        assert_eq!(weights.len(), 9);
+        let l1: f64 = weights[..6].iter().map(|w| w.data().abs()).sum();
+        let l2: f64 = weights.iter().map(|w| w.data().powi(2)).sum();
+        assert_abs_diff_eq!(mlp.penalty().data(), 0.5 * l1 + 0.1 * l2, epsilon = 1e-12);
+
+        // Biases are not penalized
+        mlp.penalty().backward();
+        assert!(mlp
+            .parameters()
+            .iter()
+            .filter(|p| p.label().unwrap().ends_with(".b"))
+            .all(|p| p.gradient() == 0.));
+    }
+
+    #[test]
+    fn test_constraints() {
+        let mlp: Mlp<3, 2> =
+            Mlp::from_layer(SizedLayer::new(Activation::Relu).with_constraint(MaxNorm::new(0.5)))
+                .add_layer(SizedLayer::<2, 2>::new(Activation::Relu))
+                .with_constraint(MaxNorm::new(1.0));
+        for p in mlp.parameters() {
+            p.set_data(2.0)
+        }
+        mlp.apply_constraints();
+        let norms: Vec<f64> = mlp
+            .layers()
+            .iter()
+            .flat_map(|l| l.weights())
+            .map(|w| w.iter().map(|w| w.data().powi(2)).sum::<f64>().sqrt())
+            .collect();
+        for (norm, expected) in norms.iter().zip([0.5, 0.5, 1.0, 1.0]) {
+            assert_abs_diff_eq!(*norm, expected, epsilon = 1e-12);
+        }
+        assert!(mlp
+            .parameters()
+            .iter()
+            .filter(|p| p.label().unwrap().ends_with(".b"))
+            .all(|p| p.data() == 2.0));
+    }
+
+    /// Train on a small dataset with SGD, returning the loss of each step
+    fn train(mlp: &Mlp<3, 1>) -> Vec<f64> {
         let dataset = [
             [Value::from(2.0), Value::from(3.0), Value::from(-1.0)],
             [Value::from(3.0), Value::from(-1.0), Value::from(0.5)],
@@ -181,19 +651,126 @@ mod tests {
             Value::from(-1.0),
             Value::from(1.0),
         ];
+        let mut optimizer = Sgd::new(mlp.parameters(), 0.1);
+        let mut losses = Vec::new();
         for _ in 0..15 {
             let loss: Value = dataset
                 .clone()
                 .into_iter()
-                .zip(truth.clone().into_iter())
+                .zip(truth.clone())
                 .map(|(d, t)| (t - mlp.forward(d)[0].clone()).powf(Value::from(2.0)))
                 .sum();
 
-            mlp.zero_grad();
+            optimizer.zero_grad();
             loss.backward();
-            for p in mlp.parameters() {
-                p.set_data(p.data() + p.gradient() * -0.1)
-            }
+            optimizer.step();
+            losses.push(loss.data());
         }
+        losses
+    }
+
+    #[test]
+    fn test_mpl_train() {
+        let losses = train(&mlp());
+        assert!(losses[14] < losses[0]);
+        // The seeded model always trains the same way
+        assert_eq!(train(&mlp()), losses);
+    }
+
+    #[test]
+    fn test_neuron_state_dict() {
+        let neuron = Neuron::<2>::new(Activation::Relu);
+        let mut state = neuron.state_dict();
+        assert_eq!(state.get("weight").unwrap().shape, vec![2]);
+        assert_eq!(state.get("bias").unwrap(), &Tensor::new(vec![], vec![0.]));
+
+        state.insert("bias", Tensor::new(vec![], vec![1.5]));
+        neuron.load_state_dict(&state).unwrap();
+        assert_eq!(neuron.bias.data(), 1.5);
+    }
+
+    #[test]
+    fn test_layer_state_dict() {
+        let layer = SizedLayer::<3, 2>::new(Activation::Relu);
+        let state = layer.state_dict();
+        let weight = state.get("weight").unwrap();
+        assert_eq!(weight.shape, vec![2, 3]);
+        assert_eq!(weight.data[3], layer.weights()[1][0].data());
+
+        let other = SizedLayer::<3, 2>::new(Activation::Relu);
+        other.load_state_dict(&state).unwrap();
+        assert_eq!(other.state_dict(), state);
+
+        let transposed = SizedLayer::<2, 3>::new(Activation::Relu);
+        assert!(matches!(
+            transposed.load_state_dict(&state),
+            Err(StateError::Shape { .. })
+        ));
+    }
+
+    #[rstest]
+    fn test_mlp_state_dict(mlp: Mlp<3, 1>) {
+        let state = mlp.state_dict();
+        assert_eq!(
+            state.names().collect::<Vec<_>>(),
+            vec![
+                "layer0.bias",
+                "layer0.weight",
+                "layer1.bias",
+                "layer1.weight",
+                "layer2.bias",
+                "layer2.weight"
+            ]
+        );
+
+        // Reloading restores the exact outputs of the trained model
+        let x = [1.0, -2.0, 0.5].map(Value::from);
+        let trained = Mlp::from_layer(SizedLayer::<3, 4>::new(Activation::Relu))
+            .add_layer(SizedLayer::<4, 4>::new(Activation::Relu))
+            .add_layer(SizedLayer::<4, 1>::new(Activation::Identity))
+            .with_seed(1);
+        train(&trained);
+        let restored = mlp;
+        let bytes = trained.state_dict().to_bytes();
+        restored
+            .load_state_dict(&StateDict::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(
+            restored.forward(x.clone())[0].data(),
+            trained.forward(x)[0].data()
+        );
+    }
+
+    #[rstest]
+    fn test_mlp_load_validation(mlp: Mlp<3, 1>) {
+        let before = data(&mlp.parameters());
+        let mut state = mlp.state_dict();
+        for p in mlp.parameters() {
+            p.set_data(0.)
+        }
+        let complete = state.clone();
+
+        state.remove("layer2.bias");
+        let err = mlp.load_state_dict(&state).unwrap_err();
+        assert_eq!(err.to_string(), "missing entry for layer2.bias");
+
+        let mut state = complete.clone();
+        state.insert("layer3.bias", Tensor::new(vec![1], vec![0.]));
+        assert!(matches!(
+            mlp.load_state_dict(&state),
+            Err(StateError::Unexpected(name)) if name == "layer3.bias"
+        ));
+
+        let mut state = complete.clone();
+        state.insert("layer1.weight", Tensor::new(vec![4, 3], vec![0.; 12]));
+        assert_eq!(
+            mlp.load_state_dict(&state).unwrap_err().to_string(),
+            "layer1.weight has shape [4, 3], expected [4, 4]"
+        );
+
+        // Failed loads leave the parameters untouched
+        assert!(mlp.parameters().iter().all(|p| p.data() == 0.));
+        mlp.load_state_dict(&complete).unwrap();
+        assert_eq!(data(&mlp.parameters()), before);
     }
 }
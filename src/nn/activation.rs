@@ -0,0 +1,115 @@
+use std::f64::consts::PI;
+
+use crate::value::Value;
+
+/// Non-linearity applied to the output of each neuron in a layer
+///
+/// `Custom` activations cannot be serialized.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Activation {
+    /// No activation, for linear layers
+    Identity,
+    #[default]
+    Relu,
+    Tanh,
+    /// `1 / (1 + e^-x)`, computed as `(tanh(x / 2) + 1) / 2`
+    Sigmoid,
+    /// `x` for positive inputs and `slope * x` otherwise
+    LeakyRelu(f64),
+    /// Gaussian error linear unit, using the tanh approximation
+    Gelu,
+    /// `ln(1 + e^x)`, computed as `max(x, 0) + ln(1 + e^-|x|)` to avoid overflow
+    Softplus,
+    /// Any other function of a single Value
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(fn(Value) -> Value),
+}
+
+/// Custom activations are equal only if they point to the same function
+impl PartialEq for Activation {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Activation::LeakyRelu(a), Activation::LeakyRelu(b)) => a == b,
+            (Activation::Custom(a), Activation::Custom(b)) => *a as usize == *b as usize,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Activation {
+    pub fn apply(self, x: Value) -> Value {
+        match self {
+            Activation::Identity => x,
+            Activation::Relu => x.relu(),
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => ((x * 0.5).tanh() + 1.0) * 0.5,
+            Activation::LeakyRelu(slope) => x.clone().relu() - (-x).relu() * slope,
+            Activation::Gelu => {
+                let cubic = x.clone() * x.clone() * x.clone() * 0.044715;
+                let inner = (x.clone() + cubic) * (2. / PI).sqrt();
+                x * (inner.tanh() + 1.0) * 0.5
+            }
+            Activation::Softplus => x.clone().relu() + ((-x.abs()).exp() + 1.0).ln(),
+            Activation::Custom(f) => f(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Activation;
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+    use rstest::rstest;
+    use std::f64::consts::PI;
+
+    fn sigmoid(x: f64) -> f64 {
+        1. / (1. + (-x).exp())
+    }
+
+    #[rstest]
+    #[case::identity(Activation::Identity, |x| x)]
+    #[case::relu(Activation::Relu, |x: f64| x.max(0.))]
+    #[case::tanh(Activation::Tanh, f64::tanh)]
+    #[case::sigmoid(Activation::Sigmoid, sigmoid)]
+    #[case::leaky_relu(Activation::LeakyRelu(0.1), |x| if x > 0. { x } else { 0.1 * x })]
+    #[case::gelu(Activation::Gelu, |x: f64| x * sigmoid((8. / PI).sqrt() * (x + 0.044715 * x.powi(3))))]
+    #[case::softplus(Activation::Softplus, |x: f64| x.exp().ln_1p())]
+    #[case::custom(Activation::Custom(|x| x.clone() * x), |x| x * x)]
+    fn test_activation(#[case] activation: Activation, #[case] expected: fn(f64) -> f64) {
+        for data in [-3.0, -0.5, 0.25, 2.0] {
+            let x = Value::from(data);
+            let y = activation.apply(x.clone());
+            assert_abs_diff_eq!(y.data(), expected(data), epsilon = 1e-12);
+
+            // Compare the gradient against central differences
+            y.backward();
+            let h = 1e-6;
+            let numeric = (expected(data + h) - expected(data - h)) / (2. * h);
+            assert_abs_diff_eq!(x.gradient(), numeric, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_softplus_is_stable() {
+        assert_eq!(
+            Activation::Softplus.apply(Value::from(1000.0)).data(),
+            1000.0
+        );
+        assert_eq!(Activation::Softplus.apply(Value::from(-1000.0)).data(), 0.0);
+    }
+
+    #[test]
+    fn test_equality() {
+        fn square(x: Value) -> Value {
+            x.clone() * x
+        }
+        assert_eq!(Activation::Relu, Activation::Relu);
+        assert_ne!(Activation::Relu, Activation::Tanh);
+        assert_eq!(Activation::LeakyRelu(0.1), Activation::LeakyRelu(0.1));
+        assert_ne!(Activation::LeakyRelu(0.1), Activation::LeakyRelu(0.2));
+        assert_eq!(Activation::Custom(square), Activation::Custom(square));
+        assert_ne!(Activation::Custom(square), Activation::Custom(|x| x.tanh()));
+    }
+}
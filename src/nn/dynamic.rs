@@ -0,0 +1,761 @@
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use std::fmt::Display;
+
+use crate::nn::activation::Activation;
+use crate::nn::init::{Initializer, Uniform};
+use crate::nn::regularization::{Constraint, Regularizer};
+use crate::nn::state::{Entry, StateDict, StateError};
+use crate::nn::{
+    constrain, initialize, layer_entries, layer_penalty, scope_labels, utils, Layer, Neuron,
+    SizedLayer,
+};
+use crate::value::Value;
+
+/// Error raised when a number of values does not match the size of a layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShapeError {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl ShapeError {
+    pub(crate) fn check(expected: usize, actual: usize) -> Result<(), ShapeError> {
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(ShapeError { expected, actual })
+        }
+    }
+}
+
+impl Display for ShapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected {} values, found {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+/// A Layer with the input and output dimensions chosen at runtime
+///
+/// Behaves like `SizedLayer`, but checks the size of its input on each forward pass.
+pub struct DynLayer {
+    inputs: usize,
+    weights: Vec<Vec<Value>>,
+    biases: Vec<Value>,
+    activation: Activation,
+    initializer: Box<dyn Initializer>,
+    regularizers: Vec<Box<dyn Regularizer>>,
+    constraints: Vec<Box<dyn Constraint>>,
+}
+
+impl DynLayer {
+    /// Create a layer of the provided size, initialized with random weights
+    ///
+    /// Parameters are labelled by neuron, e.g. `neuron3.w0`
+    pub fn new(inputs: usize, outputs: usize, activation: Activation) -> Self {
+        Self::new_with_rng(inputs, outputs, activation, &mut thread_rng())
+    }
+
+    /// Create a layer with weights drawn from the provided random number
+    /// generator, in the same order as `SizedLayer::new_with_rng`
+    pub fn new_with_rng(
+        inputs: usize,
+        outputs: usize,
+        activation: Activation,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let weights = (0..outputs)
+            .map(|i| {
+                (0..inputs)
+                    .map(|j| Value::named(&format!("neuron{i}.w{j}"), rng.gen_range(-1.0..1.0)))
+                    .collect()
+            })
+            .collect();
+        Self {
+            inputs,
+            weights,
+            biases: (0..outputs)
+                .map(|i| Value::named(&format!("neuron{i}.b"), 0.))
+                .collect(),
+            activation,
+            initializer: Box::new(Uniform::default()),
+            regularizers: Vec::new(),
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Re-initialize the weights with a different scheme, which is also used
+    /// by any later `reset_parameters`
+    pub fn with_initializer(self, initializer: impl Initializer + 'static) -> Self {
+        self.with_initializer_and_rng(initializer, &mut thread_rng())
+    }
+
+    /// Re-initialize the weights with a different scheme, drawing from the
+    /// provided random number generator
+    pub fn with_initializer_and_rng(
+        mut self,
+        initializer: impl Initializer + 'static,
+        rng: &mut dyn RngCore,
+    ) -> Self {
+        self.initializer = Box::new(initializer);
+        self.reset_parameters(rng);
+        self
+    }
+
+    /// Penalize the weights of this layer, excluding biases
+    pub fn with_regularizer(mut self, regularizer: impl Regularizer + 'static) -> Self {
+        self.regularizers.push(Box::new(regularizer));
+        self
+    }
+
+    /// Constrain the weights of each neuron in this layer
+    pub fn with_constraint(mut self, constraint: impl Constraint + 'static) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
+}
+
+impl Layer for DynLayer {
+    /// Panics if the number of inputs does not match the layer, see `try_forward`
+    fn forward(&self, x: Vec<Value>) -> Vec<Value> {
+        self.try_forward(&x).unwrap()
+    }
+
+    fn try_forward(&self, x: &[Value]) -> Result<Vec<Value>, ShapeError> {
+        ShapeError::check(self.inputs, x.len())?;
+        Ok(self
+            .weights
+            .iter()
+            .zip(&self.biases)
+            .map(|(w, b)| self.activation.apply(Value::dot(w, x) + b.clone()))
+            .collect())
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        self.weights
+            .iter()
+            .zip(&self.biases)
+            .flat_map(|(w, b)| w.iter().chain([b]).cloned())
+            .collect()
+    }
+
+    fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    fn outputs(&self) -> usize {
+        self.biases.len()
+    }
+
+    fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    fn weights(&self) -> Vec<Vec<Value>> {
+        self.weights.clone()
+    }
+
+    fn biases(&self) -> Vec<Value> {
+        self.biases.clone()
+    }
+
+    fn penalty(&self) -> Option<Value> {
+        layer_penalty(&self.regularizers, &self.weights)
+    }
+
+    fn apply_constraints(&self) {
+        constrain(&self.constraints, &self.weights)
+    }
+
+    fn reset_parameters(&self, rng: &mut dyn RngCore) {
+        initialize(
+            self.initializer.as_ref(),
+            &self.weights,
+            &self.biases,
+            self.inputs,
+            rng,
+        )
+    }
+}
+
+/// The converted layer shares its parameters with the original
+impl<const I: usize, const O: usize> From<SizedLayer<I, O>> for DynLayer {
+    fn from(value: SizedLayer<I, O>) -> Self {
+        let activation = value.activation();
+        let (weights, biases) = value
+            .neurons
+            .into_iter()
+            .map(|n| (n.weights.to_vec(), n.bias))
+            .unzip();
+        Self {
+            inputs: I,
+            weights,
+            biases,
+            activation,
+            initializer: value.initializer,
+            regularizers: value.regularizers,
+            constraints: value.constraints,
+        }
+    }
+}
+
+impl<const I: usize, const O: usize> TryFrom<DynLayer> for SizedLayer<I, O> {
+    type Error = ShapeError;
+
+    /// Check the number of inputs and outputs of a DynLayer
+    fn try_from(value: DynLayer) -> Result<Self, Self::Error> {
+        ShapeError::check(I, value.inputs)?;
+        ShapeError::check(O, value.outputs())?;
+        let activation = value.activation;
+        Ok(Self {
+            neurons: value
+                .weights
+                .into_iter()
+                .zip(value.biases)
+                .map(|(w, bias)| Neuron {
+                    weights: w.try_into().unwrap(),
+                    bias,
+                    activation,
+                })
+                .collect::<Vec<Neuron<I>>>()
+                .try_into()
+                .unwrap(),
+            initializer: value.initializer,
+            regularizers: value.regularizers,
+            constraints: value.constraints,
+        })
+    }
+}
+
+/// Architecture of a single layer within an `MlpConfig`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerConfig {
+    pub outputs: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub activation: Activation,
+}
+
+/// Serializable architecture of a `DynMlp`, without its parameters
+///
+/// For example, as JSON:
+///
+/// ```json
+/// {"inputs": 2, "layers": [{"outputs": 16, "activation": "Relu"}, {"outputs": 1, "activation": "Identity"}]}
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MlpConfig {
+    pub inputs: usize,
+    pub layers: Vec<LayerConfig>,
+}
+
+/// Error raised when reading or writing an MlpConfig
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum ConfigError {
+    Json(serde_json::Error),
+    #[cfg(feature = "toml")]
+    TomlDe(toml::de::Error),
+    #[cfg(feature = "toml")]
+    TomlSer(toml::ser::Error),
+}
+
+#[cfg(feature = "serde")]
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Json(e) => write!(f, "{e}"),
+            #[cfg(feature = "toml")]
+            ConfigError::TomlDe(e) => write!(f, "{e}"),
+            #[cfg(feature = "toml")]
+            ConfigError::TomlSer(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for ConfigError {}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for ConfigError {
+    fn from(value: serde_json::Error) -> Self {
+        ConfigError::Json(value)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::de::Error> for ConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        ConfigError::TomlDe(value)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::ser::Error> for ConfigError {
+    fn from(value: toml::ser::Error) -> Self {
+        ConfigError::TomlSer(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl MlpConfig {
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Fails for layers with an `Activation::Custom`, which cannot be serialized
+    pub fn to_json(&self) -> Result<String, ConfigError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl MlpConfig {
+    /// Read a configuration with the layers as an array of tables, e.g.
+    ///
+    /// ```toml
+    /// inputs = 2
+    ///
+    /// [[layers]]
+    /// outputs = 16
+    /// activation = "Relu"
+    /// ```
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Fails for layers with an `Activation::Custom`, which cannot be serialized
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+/// A multi-layer perceptron with the input and output dimensions chosen at runtime
+///
+/// Layers may be `SizedLayer` or `DynLayer`, with the size of each checked as
+/// it is added.
+pub struct DynMlp {
+    inputs: usize,
+    layers: Vec<Box<dyn Layer>>,
+    regularizers: Vec<Box<dyn Regularizer>>,
+    constraints: Vec<Box<dyn Constraint>>,
+}
+
+impl DynMlp {
+    /// Create an Mlp without any layers, which passes its inputs through unchanged
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            inputs,
+            layers: Vec::new(),
+            regularizers: Vec::new(),
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Create a new Mlp from an initial layer
+    ///
+    /// Parameters of each layer are labelled by position, e.g. `layer1.neuron3.w0`
+    pub fn from_layer(layer: impl Layer + 'static) -> Self {
+        Self::new(layer.inputs()).add_layer(layer).unwrap()
+    }
+
+    /// Build the layers of a configuration with uniformly random weights
+    pub fn from_config(config: &MlpConfig) -> Self {
+        Self::from_config_with_rng(config, &mut thread_rng())
+    }
+
+    /// Build the layers of a configuration with weights drawn from the
+    /// provided random number generator
+    pub fn from_config_with_rng(config: &MlpConfig, rng: &mut impl Rng) -> Self {
+        let mut mlp = Self::new(config.inputs);
+        for layer in &config.layers {
+            let layer = DynLayer::new_with_rng(mlp.outputs(), layer.outputs, layer.activation, rng);
+            mlp = mlp.add_layer(layer).unwrap();
+        }
+        mlp
+    }
+
+    /// The architecture of the Mlp, without its parameters
+    pub fn config(&self) -> MlpConfig {
+        MlpConfig {
+            inputs: self.inputs,
+            layers: self
+                .layers
+                .iter()
+                .map(|l| LayerConfig {
+                    outputs: l.outputs(),
+                    activation: l.activation(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Add a layer to the Mlp, which must have as many inputs as the Mlp has outputs
+    pub fn add_layer(mut self, layer: impl Layer + 'static) -> Result<Self, ShapeError> {
+        ShapeError::check(self.outputs(), layer.inputs())?;
+        scope_labels(&layer.parameters(), &format!("layer{}", self.layers.len()));
+        self.layers.push(Box::new(layer));
+        Ok(self)
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.layers.last().map_or(self.inputs, |l| l.outputs())
+    }
+
+    /// The layers of the Mlp, in order
+    pub fn layers(&self) -> &[Box<dyn Layer>] {
+        &self.layers
+    }
+
+    /// Create a prediction by evaluating an input through a forward pass of
+    /// each layer, checking the number of inputs and outputs of every layer
+    pub fn forward(&self, x: &[Value]) -> Result<Vec<Value>, ShapeError> {
+        ShapeError::check(self.inputs, x.len())?;
+        let mut x = x.to_vec();
+        for layer in &self.layers {
+            x = layer.try_forward(&x)?
+        }
+        Ok(x)
+    }
+
+    /// Complete list of parameters in the Mlp graph
+    pub fn parameters(&self) -> Vec<Value> {
+        self.layers.iter().flat_map(|l| l.parameters()).collect()
+    }
+
+    /// Set all parameter gradients back to zero
+    pub fn zero_grad(&self) {
+        for p in self.parameters() {
+            p.zero_grad()
+        }
+    }
+
+    /// Penalize the weights of every layer, excluding biases
+    pub fn with_regularizer(mut self, regularizer: impl Regularizer + 'static) -> Self {
+        self.regularizers.push(Box::new(regularizer));
+        self
+    }
+
+    /// Constrain the weights of every neuron
+    pub fn with_constraint(mut self, constraint: impl Constraint + 'static) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
+
+    /// Total penalty of the regularizers attached to the Mlp and its layers,
+    /// to be added to the loss
+    pub fn penalty(&self) -> Value {
+        let weights: Vec<Value> = self
+            .layers
+            .iter()
+            .flat_map(|l| l.weights())
+            .flatten()
+            .collect();
+        self.regularizers
+            .iter()
+            .map(|r| r.penalty(&weights))
+            .chain(self.layers.iter().filter_map(|l| l.penalty()))
+            .sum()
+    }
+
+    /// Enforce the constraints attached to the Mlp and its layers, typically
+    /// after each optimizer step
+    pub fn apply_constraints(&self) {
+        for layer in &self.layers {
+            constrain(&self.constraints, &layer.weights());
+            layer.apply_constraints()
+        }
+    }
+
+    /// Re-initialize the parameters of every layer from a random number generator
+    pub fn reset_parameters(&self, rng: &mut dyn RngCore) {
+        for layer in &self.layers {
+            layer.reset_parameters(rng)
+        }
+    }
+
+    /// Re-initialize the parameters from a seed, so that the same seed always
+    /// gives the same model
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.reset_parameters(&mut StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Global L2 norm of the parameter gradients of each layer
+    pub fn layer_grad_norms(&self) -> Vec<f64> {
+        self.layers
+            .iter()
+            .map(|l| utils::grad_norm(&l.parameters()))
+            .collect()
+    }
+
+    /// Snapshot of the parameters, with the entries of each layer scoped by
+    /// position, e.g. `layer0.weight`
+    pub fn state_dict(&self) -> StateDict {
+        StateDict::from_entries(&self.entries())
+    }
+
+    /// Copy the parameters from a snapshot created by `state_dict`, which must
+    /// have an entry of the same shape for every parameter and nothing else
+    pub fn load_state_dict(&self, state: &StateDict) -> Result<(), StateError> {
+        state.load_entries(&self.entries())
+    }
+
+    fn entries(&self) -> Vec<Entry> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, l)| {
+                layer_entries(l.as_ref())
+                    .into_iter()
+                    .map(move |e| e.scoped(&format!("layer{i}")))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynLayer, DynMlp, LayerConfig, MlpConfig, ShapeError};
+    use crate::nn::activation::Activation;
+    use crate::nn::{Layer, Mlp, SizedLayer};
+    use crate::value::Value;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rstest::{fixture, rstest};
+
+    fn values(data: &[f64]) -> Vec<Value> {
+        data.iter().copied().map(Value::from).collect()
+    }
+
+    #[fixture]
+    fn config() -> MlpConfig {
+        MlpConfig {
+            inputs: 3,
+            layers: vec![
+                LayerConfig {
+                    outputs: 4,
+                    activation: Activation::Tanh,
+                },
+                LayerConfig {
+                    outputs: 2,
+                    activation: Activation::Identity,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_layer_shape() {
+        let layer = DynLayer::new(3, 2, Activation::Relu);
+        assert_eq!((layer.inputs(), layer.outputs()), (3, 2));
+        assert_eq!(
+            layer.try_forward(&values(&[1.0, 2.0, 3.0])).unwrap().len(),
+            2
+        );
+        assert_eq!(
+            layer.try_forward(&values(&[1.0])).unwrap_err(),
+            ShapeError {
+                expected: 3,
+                actual: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_matches_sized_layer() {
+        let sized =
+            SizedLayer::<3, 2>::new_with_rng(Activation::Tanh, &mut StdRng::seed_from_u64(0));
+        let dynamic = DynLayer::new_with_rng(3, 2, Activation::Tanh, &mut StdRng::seed_from_u64(0));
+        let data = |l: &dyn Layer| -> Vec<(Option<String>, f64)> {
+            l.parameters()
+                .iter()
+                .map(|p| (p.label(), p.data()))
+                .collect()
+        };
+        assert_eq!(data(&sized), data(&dynamic));
+    }
+
+    #[rstest]
+    fn test_mlp_from_config(config: MlpConfig) {
+        let mlp = DynMlp::from_config(&config);
+        assert_eq!((mlp.inputs(), mlp.outputs()), (3, 2));
+        assert_eq!(mlp.parameters().len(), 4 * 4 + 2 * 5);
+        assert_eq!(mlp.config(), config);
+        assert_eq!(
+            mlp.parameters()[0].label(),
+            Some("layer0.neuron0.w0".to_string())
+        );
+        assert_eq!(mlp.forward(&values(&[1.0, 2.0, 3.0])).unwrap().len(), 2);
+        assert!(mlp.forward(&values(&[1.0, 2.0])).is_err());
+    }
+
+    #[test]
+    fn test_mlp_layer_mismatch() {
+        let mlp = DynMlp::from_layer(DynLayer::new(3, 4, Activation::Relu));
+        let err = mlp
+            .add_layer(SizedLayer::<3, 1>::new(Activation::Relu))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "expected 4 values, found 3");
+    }
+
+    /// A layer that claims one more output than it produces
+    struct Truncated(DynLayer);
+
+    impl Layer for Truncated {
+        fn forward(&self, x: Vec<Value>) -> Vec<Value> {
+            self.0.forward(x)
+        }
+
+        fn parameters(&self) -> Vec<Value> {
+            self.0.parameters()
+        }
+
+        fn inputs(&self) -> usize {
+            self.0.inputs()
+        }
+
+        fn outputs(&self) -> usize {
+            self.0.outputs() + 1
+        }
+
+        fn activation(&self) -> Activation {
+            self.0.activation()
+        }
+
+        fn weights(&self) -> Vec<Vec<Value>> {
+            self.0.weights()
+        }
+
+        fn biases(&self) -> Vec<Value> {
+            self.0.biases()
+        }
+
+        fn reset_parameters(&self, rng: &mut dyn rand::RngCore) {
+            self.0.reset_parameters(rng)
+        }
+    }
+
+    #[test]
+    fn test_mlp_checks_every_layer() {
+        let mlp = DynMlp::from_layer(DynLayer::new(3, 4, Activation::Relu))
+            .add_layer(Truncated(DynLayer::new(4, 1, Activation::Relu)))
+            .unwrap()
+            .add_layer(DynLayer::new(2, 1, Activation::Identity))
+            .unwrap();
+        assert_eq!(
+            mlp.forward(&values(&[1.0, 2.0, 3.0])).unwrap_err(),
+            ShapeError {
+                expected: 2,
+                actual: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_layer_conversion() {
+        let sized = SizedLayer::<2, 3>::new(Activation::Tanh);
+        let parameters = sized.parameters();
+        let dynamic = DynLayer::from(sized);
+        assert_eq!(
+            dynamic.activation().apply(Value::from(0.5)).data(),
+            0.5_f64.tanh()
+        );
+
+        // Parameters are shared, not copied
+        dynamic.parameters()[0].set_data(10.0);
+        assert_eq!(parameters[0].data(), 10.0);
+
+        let x = values(&[0.5, -1.0]);
+        let expected: Vec<f64> = dynamic
+            .forward(x.clone())
+            .iter()
+            .map(|v| v.data())
+            .collect();
+        assert!(SizedLayer::<3, 3>::try_from(DynLayer::new(2, 3, Activation::Relu)).is_err());
+        let sized = SizedLayer::<2, 3>::try_from(dynamic).unwrap();
+        let actual: Vec<f64> = sized.forward(x).iter().map(|v| v.data()).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    fn test_mlp_conversion(config: MlpConfig) {
+        let mlp: Mlp<3, 2> = Mlp::try_from(DynMlp::from_config(&config)).unwrap();
+        let x = [1.0, -2.0, 0.5].map(Value::from);
+        let expected: Vec<f64> = mlp.forward(x.clone()).iter().map(|v| v.data()).collect();
+
+        let dynamic = DynMlp::from(mlp);
+        assert_eq!(dynamic.config(), config);
+        let actual: Vec<f64> = dynamic
+            .forward(&x)
+            .unwrap()
+            .iter()
+            .map(|v| v.data())
+            .collect();
+        assert_eq!(actual, expected);
+        assert!(Mlp::<3, 1>::try_from(dynamic).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_json_config(config: MlpConfig) {
+        let json = r#"{"inputs": 3, "layers": [{"outputs": 4, "activation": "Tanh"}, {"outputs": 2, "activation": "Identity"}]}"#;
+        assert_eq!(MlpConfig::from_json(json).unwrap(), config);
+        assert_eq!(
+            MlpConfig::from_json(&config.to_json().unwrap()).unwrap(),
+            config
+        );
+
+        // Layers without an activation use the default
+        let config = MlpConfig::from_json(r#"{"inputs": 1, "layers": [{"outputs": 1}]}"#).unwrap();
+        assert_eq!(config.layers[0].activation, Activation::Relu);
+        let config = MlpConfig::from_json(
+            r#"{"inputs": 1, "layers": [{"outputs": 1, "activation": {"LeakyRelu": 0.1}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.layers[0].activation, Activation::LeakyRelu(0.1));
+
+        assert!(MlpConfig::from_json(r#"{"inputs": 1}"#).is_err());
+        let custom = MlpConfig {
+            inputs: 1,
+            layers: vec![LayerConfig {
+                outputs: 1,
+                activation: Activation::Custom(|x| x),
+            }],
+        };
+        assert!(custom.to_json().is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[rstest]
+    fn test_toml_config(config: MlpConfig) {
+        let toml = r#"
+            inputs = 3
+
+            [[layers]]
+            outputs = 4
+            activation = "Tanh"
+
+            [[layers]]
+            outputs = 2
+            activation = "Identity"
+        "#;
+        assert_eq!(MlpConfig::from_toml(toml).unwrap(), config);
+        assert_eq!(
+            MlpConfig::from_toml(&config.to_toml().unwrap()).unwrap(),
+            config
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::value::Value;
+
+/// The largest of the Values, used to shift inputs of `exp` into a safe range
+///
+/// Built from graph operations as `max(a, b) = b + relu(a - b)`, so that the
+/// shift follows the inputs when the graph is recomputed
+fn max(x: &[Value]) -> Value {
+    let largest = x.iter().map(|v| v.data()).fold(f64::NEG_INFINITY, f64::max);
+    // Shifting by an infinite value would turn every input into NaN
+    if !largest.is_finite() {
+        return Value::from(0.);
+    }
+    x[1..].iter().fold(x[0].clone(), |max, v| {
+        max.clone() + (v.clone() - max).relu()
+    })
+}
+
+/// Numerically stable `ln(sum(exp(x)))`
+///
+/// The largest input is subtracted before exponentiating so that no term
+/// overflows. The shift cancels out of both the result and the gradient.
+pub fn logsumexp(x: &[Value]) -> Value {
+    let max = max(x);
+    x.iter()
+        .map(|v| (v.clone() - max.clone()).exp())
+        .sum::<Value>()
+        .ln()
+        + max
+}
+
+/// Numerically stable logarithm of the softmax of the Values
+pub fn log_softmax(x: &[Value]) -> Vec<Value> {
+    let lse = logsumexp(x);
+    x.iter().map(|v| v.clone() - lse.clone()).collect()
+}
+
+/// Normalize Values into a probability distribution, `exp(x_i) / sum(exp(x))`
+///
+/// Computed as the exponent of `log_softmax` so that large inputs do not overflow
+pub fn softmax(x: &[Value]) -> Vec<Value> {
+    log_softmax(x).into_iter().map(|v| v.exp()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{log_softmax, logsumexp, softmax};
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+
+    fn values(data: &[f64]) -> Vec<Value> {
+        data.iter().copied().map(Value::from).collect()
+    }
+
+    #[test]
+    fn test_softmax() {
+        let x = values(&[1.0, 2.0, 3.0]);
+        let total: f64 = [1.0_f64, 2.0, 3.0].iter().map(|v| v.exp()).sum();
+        let s = softmax(&x);
+        for (p, v) in s.iter().zip([1.0_f64, 2.0, 3.0]) {
+            assert_abs_diff_eq!(p.data(), v.exp() / total, epsilon = 1e-12);
+        }
+        assert_abs_diff_eq!(logsumexp(&x).data(), total.ln(), epsilon = 1e-12);
+
+        // d softmax_0 / d x_j = s_0 * (delta_0j - s_j)
+        s[0].backward();
+        let s: Vec<f64> = s.iter().map(|v| v.data()).collect();
+        assert_abs_diff_eq!(x[0].gradient(), s[0] * (1.0 - s[0]), epsilon = 1e-12);
+        assert_abs_diff_eq!(x[1].gradient(), -s[0] * s[1], epsilon = 1e-12);
+        assert_abs_diff_eq!(x[2].gradient(), -s[0] * s[2], epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_large_logits() {
+        let x = values(&[1000.0, 1001.0, -1000.0]);
+        let s = softmax(&x);
+        assert!(s.iter().all(|v| v.data().is_finite()));
+        assert_abs_diff_eq!(
+            s.iter().map(|v| v.data()).sum::<f64>(),
+            1.0,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            logsumexp(&x).data(),
+            1001.0 + (1.0 + (-1.0_f64).exp()).ln(),
+            epsilon = 1e-9
+        );
+
+        let l = log_softmax(&x);
+        assert_abs_diff_eq!(
+            l[2].data(),
+            -2001.0 - (1.0 + (-1.0_f64).exp()).ln(),
+            epsilon = 1e-9
+        );
+        l[1].backward();
+        // d log_softmax_1 / d x_j = delta_1j - s_j
+        assert_abs_diff_eq!(x[0].gradient(), -s[0].data(), epsilon = 1e-12);
+        assert_abs_diff_eq!(x[1].gradient(), 1.0 - s[1].data(), epsilon = 1e-12);
+        assert!(x[2].gradient().is_finite());
+    }
+
+    #[test]
+    fn test_recompute() {
+        let x = values(&[0.0, 1.0, 2.0]);
+        let lse = logsumexp(&x);
+        // The shift follows the new largest input instead of overflowing
+        x[0].set_data(1000.0);
+        assert_abs_diff_eq!(
+            lse.recompute(),
+            1000.0 + (1.0 + (-999.0_f64).exp() + (-998.0_f64).exp()).ln(),
+            epsilon = 1e-9
+        );
+        lse.backward();
+        assert_abs_diff_eq!(x[0].gradient(), 1.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(x[2].gradient(), (-998.0_f64).exp(), epsilon = 1e-12);
+    }
+}
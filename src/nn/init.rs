@@ -0,0 +1,302 @@
+use rand::{Rng, RngCore};
+use rand_distr::{Distribution, Normal};
+use std::fmt::Debug;
+
+/// A scheme for drawing the initial weights of a layer
+pub trait Initializer: Debug {
+    /// Weights of a layer with `fan_in` inputs and `fan_out` neurons, as one
+    /// row of `fan_in` weights per neuron
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>>;
+}
+
+/// Fill a `fan_out` by `fan_in` matrix with a closure
+fn matrix(fan_in: usize, fan_out: usize, mut f: impl FnMut() -> f64) -> Vec<Vec<f64>> {
+    (0..fan_out)
+        .map(|_| (0..fan_in).map(|_| f()).collect())
+        .collect()
+}
+
+fn uniform(fan_in: usize, fan_out: usize, limit: f64, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+    if limit == 0. {
+        return matrix(fan_in, fan_out, || 0.);
+    }
+    matrix(fan_in, fan_out, || rng.gen_range(-limit..limit))
+}
+
+fn normal(fan_in: usize, fan_out: usize, stddev: f64, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+    let normal = Normal::new(0., stddev).expect("Standard deviation must be finite");
+    matrix(fan_in, fan_out, || normal.sample(rng))
+}
+
+/// Weights drawn uniformly from `[low, high)`, by default `[-1, 1)`
+#[derive(Debug, Clone, Copy)]
+pub struct Uniform {
+    low: f64,
+    high: f64,
+}
+
+impl Uniform {
+    pub fn new(low: f64, high: f64) -> Self {
+        assert!(low < high, "Uniform range must not be empty");
+        Self { low, high }
+    }
+}
+
+impl Default for Uniform {
+    fn default() -> Self {
+        Self::new(-1.0, 1.0)
+    }
+}
+
+impl Initializer for Uniform {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        matrix(fan_in, fan_out, || rng.gen_range(self.low..self.high))
+    }
+}
+
+/// Xavier/Glorot uniform, `U(-a, a)` with `a = gain * sqrt(6 / (fan_in + fan_out))`
+///
+/// Suited to `tanh` and linear layers.
+#[derive(Debug, Clone, Copy)]
+pub struct XavierUniform {
+    gain: f64,
+}
+
+impl XavierUniform {
+    pub fn new(gain: f64) -> Self {
+        Self { gain }
+    }
+}
+
+impl Default for XavierUniform {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Initializer for XavierUniform {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        let limit = self.gain * (6. / (fan_in + fan_out) as f64).sqrt();
+        uniform(fan_in, fan_out, limit, rng)
+    }
+}
+
+/// Xavier/Glorot normal, `N(0, std^2)` with `std = gain * sqrt(2 / (fan_in + fan_out))`
+#[derive(Debug, Clone, Copy)]
+pub struct XavierNormal {
+    gain: f64,
+}
+
+impl XavierNormal {
+    pub fn new(gain: f64) -> Self {
+        Self { gain }
+    }
+}
+
+impl Default for XavierNormal {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Initializer for XavierNormal {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        let stddev = self.gain * (2. / (fan_in + fan_out) as f64).sqrt();
+        normal(fan_in, fan_out, stddev, rng)
+    }
+}
+
+/// He/Kaiming uniform, `U(-a, a)` with `a = sqrt(6 / fan_in)`
+///
+/// Suited to ReLU layers.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeUniform;
+
+impl Initializer for HeUniform {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        uniform(fan_in, fan_out, (6. / fan_in as f64).sqrt(), rng)
+    }
+}
+
+/// He/Kaiming normal, `N(0, 2 / fan_in)`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeNormal;
+
+impl Initializer for HeNormal {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        normal(fan_in, fan_out, (2. / fan_in as f64).sqrt(), rng)
+    }
+}
+
+/// LeCun uniform, `U(-a, a)` with `a = sqrt(3 / fan_in)`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LeCunUniform;
+
+impl Initializer for LeCunUniform {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        uniform(fan_in, fan_out, (3. / fan_in as f64).sqrt(), rng)
+    }
+}
+
+/// LeCun normal, `N(0, 1 / fan_in)`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LeCunNormal;
+
+impl Initializer for LeCunNormal {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        normal(fan_in, fan_out, (1. / fan_in as f64).sqrt(), rng)
+    }
+}
+
+/// A random matrix with orthonormal rows, or orthonormal columns when there
+/// are more neurons than inputs, scaled by `gain`
+///
+/// Built by Gram-Schmidt orthogonalization of normally distributed vectors.
+#[derive(Debug, Clone, Copy)]
+pub struct Orthogonal {
+    gain: f64,
+}
+
+impl Orthogonal {
+    pub fn new(gain: f64) -> Self {
+        Self { gain }
+    }
+}
+
+impl Default for Orthogonal {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+/// Orthonormalize a set of vectors in place
+fn gram_schmidt(vectors: &mut [Vec<f64>]) {
+    for i in 0..vectors.len() {
+        let (done, rest) = vectors.split_at_mut(i);
+        let v = &mut rest[0];
+        for u in done.iter() {
+            let projection: f64 = u.iter().zip(v.iter()).map(|(u, v)| u * v).sum();
+            for (v, u) in v.iter_mut().zip(u) {
+                *v -= projection * u
+            }
+        }
+        let norm = v.iter().map(|v| v * v).sum::<f64>().sqrt();
+        for v in v.iter_mut() {
+            *v /= norm
+        }
+    }
+}
+
+impl Initializer for Orthogonal {
+    fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        let mut w = normal(fan_in, fan_out, 1., rng);
+        if fan_out <= fan_in {
+            gram_schmidt(&mut w);
+        } else {
+            let mut columns: Vec<Vec<f64>> = (0..fan_in)
+                .map(|j| w.iter().map(|r| r[j]).collect())
+                .collect();
+            gram_schmidt(&mut columns);
+            w = (0..fan_out)
+                .map(|i| columns.iter().map(|c| c[i]).collect())
+                .collect();
+        }
+        for row in w.iter_mut() {
+            for x in row.iter_mut() {
+                *x *= self.gain
+            }
+        }
+        w
+    }
+}
+
+/// Every weight set to the same value
+#[derive(Debug, Clone, Copy)]
+pub struct Constant {
+    value: f64,
+}
+
+impl Constant {
+    pub fn new(value: f64) -> Self {
+        Self { value }
+    }
+}
+
+impl Initializer for Constant {
+    fn weights(&self, fan_in: usize, fan_out: usize, _rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        matrix(fan_in, fan_out, || self.value)
+    }
+}
+
+/// Every weight set to zero
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Zeros;
+
+impl Initializer for Zeros {
+    fn weights(&self, fan_in: usize, fan_out: usize, _rng: &mut dyn RngCore) -> Vec<Vec<f64>> {
+        matrix(fan_in, fan_out, || 0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Constant, HeNormal, HeUniform, Initializer, LeCunNormal, LeCunUniform, Orthogonal, Uniform,
+        XavierNormal, XavierUniform, Zeros,
+    };
+    use approx::assert_abs_diff_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rstest::rstest;
+
+    fn variance(w: &[Vec<f64>]) -> f64 {
+        let values = w.concat();
+        values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64
+    }
+
+    #[rstest]
+    #[case::uniform(&Uniform::default(), 1. / 3.)]
+    #[case::xavier_uniform(&XavierUniform::default(), 2. / 300.)]
+    #[case::xavier_normal(&XavierNormal::new(2.0), 8. / 300.)]
+    #[case::he_uniform(&HeUniform, 2. / 100.)]
+    #[case::he_normal(&HeNormal, 2. / 100.)]
+    #[case::lecun_uniform(&LeCunUniform, 1. / 100.)]
+    #[case::lecun_normal(&LeCunNormal, 1. / 100.)]
+    fn test_variance(#[case] init: &dyn Initializer, #[case] expected: f64) {
+        let w = init.weights(100, 200, &mut StdRng::seed_from_u64(0));
+        assert_eq!(w.len(), 200);
+        assert!(w.iter().all(|row| row.len() == 100));
+        assert_abs_diff_eq!(variance(&w), expected, epsilon = 0.05 * expected);
+    }
+
+    #[rstest]
+    #[case::wide(5, 3)]
+    #[case::tall(3, 5)]
+    fn test_orthogonal(#[case] fan_in: usize, #[case] fan_out: usize) {
+        let w = Orthogonal::new(2.0).weights(fan_in, fan_out, &mut StdRng::seed_from_u64(0));
+        let (vectors, length): (Vec<Vec<f64>>, usize) = if fan_out <= fan_in {
+            (w, fan_in)
+        } else {
+            (
+                (0..fan_in)
+                    .map(|j| w.iter().map(|r| r[j]).collect())
+                    .collect(),
+                fan_out,
+            )
+        };
+        for (i, a) in vectors.iter().enumerate() {
+            assert_eq!(a.len(), length);
+            for (j, b) in vectors.iter().enumerate() {
+                let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+                assert_abs_diff_eq!(dot, if i == j { 4.0 } else { 0.0 }, epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant() {
+        let rng = &mut StdRng::seed_from_u64(0);
+        assert_eq!(Constant::new(0.5).weights(2, 1, rng), vec![vec![0.5, 0.5]]);
+        assert_eq!(Zeros.weights(1, 2, rng), vec![vec![0.], vec![0.]]);
+    }
+}
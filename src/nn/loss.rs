@@ -0,0 +1,389 @@
+use std::f64::consts::PI;
+
+use crate::nn::functional::log_softmax;
+use crate::value::Value;
+
+/// How the loss terms of a prediction are combined
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    /// Average of all terms
+    #[default]
+    Mean,
+    /// Sum of all terms
+    Sum,
+    /// Keep each term separately
+    None,
+}
+
+impl Reduction {
+    /// Combine loss terms, returning a single Value unless the reduction is `None`
+    ///
+    /// The mean of no terms is zero, matching their sum
+    pub fn apply(self, terms: Vec<Value>) -> Vec<Value> {
+        match self {
+            Reduction::Mean if terms.is_empty() => vec![Value::from(0.)],
+            Reduction::Mean => {
+                let n = terms.len() as f64;
+                vec![terms.into_iter().sum::<Value>() / n]
+            }
+            Reduction::Sum => vec![terms.into_iter().sum()],
+            Reduction::None => terms,
+        }
+    }
+}
+
+/// A measure of how far a prediction, such as the output of an `Mlp`, is from its target
+pub trait Loss {
+    /// Unreduced loss terms of a prediction
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value>;
+
+    /// How the terms are combined by `forward`
+    fn reduction(&self) -> Reduction;
+
+    /// Loss terms of a prediction after applying the reduction
+    fn forward(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        self.reduction().apply(self.terms(prediction, target))
+    }
+
+    /// The loss as a single Value, summing any terms left by `Reduction::None`
+    fn loss(&self, prediction: &[Value], target: &[Value]) -> Value {
+        self.forward(prediction, target).into_iter().sum()
+    }
+}
+
+/// Apply a loss elementwise to each pair of prediction and target
+fn elementwise(
+    prediction: &[Value],
+    target: &[Value],
+    f: impl Fn(Value, Value) -> Value,
+) -> Vec<Value> {
+    assert_eq!(
+        prediction.len(),
+        target.len(),
+        "Prediction and target have different lengths"
+    );
+    prediction
+        .iter()
+        .zip(target)
+        .map(|(p, t)| f(p.clone(), t.clone()))
+        .collect()
+}
+
+/// Mean squared error, `(prediction - target)^2`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MseLoss {
+    reduction: Reduction,
+}
+
+impl MseLoss {
+    pub fn new(reduction: Reduction) -> Self {
+        Self { reduction }
+    }
+}
+
+impl Loss for MseLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        elementwise(prediction, target, |p, t| {
+            let d = p - t;
+            d.clone() * d
+        })
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+/// Mean absolute error, `|prediction - target|`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MaeLoss {
+    reduction: Reduction,
+}
+
+impl MaeLoss {
+    pub fn new(reduction: Reduction) -> Self {
+        Self { reduction }
+    }
+}
+
+impl Loss for MaeLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        elementwise(prediction, target, |p, t| (p - t).abs())
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+/// Squared error for differences within `delta`, and absolute error beyond it
+///
+/// Computed as `0.5 * q^2 + delta * (|d| - q)` with `q = min(|d|, delta)`, so
+/// that the graph picks the right branch when it is recomputed
+#[derive(Debug, Clone, Copy)]
+pub struct HuberLoss {
+    delta: f64,
+    reduction: Reduction,
+}
+
+impl HuberLoss {
+    pub fn new(delta: f64, reduction: Reduction) -> Self {
+        Self { delta, reduction }
+    }
+}
+
+impl Default for HuberLoss {
+    fn default() -> Self {
+        Self::new(1.0, Reduction::default())
+    }
+}
+
+impl Loss for HuberLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        elementwise(prediction, target, |p, t| {
+            let d = (p - t).abs();
+            // min(|d|, delta) = delta - relu(delta - |d|)
+            let q = Value::from(self.delta) - (Value::from(self.delta) - d.clone()).relu();
+            q.clone() * q.clone() * 0.5 + (d - q) * self.delta
+        })
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+/// SVM "max-margin" loss, `max(0, margin - prediction * target)` for targets of -1 or 1
+#[derive(Debug, Clone, Copy)]
+pub struct HingeLoss {
+    margin: f64,
+    reduction: Reduction,
+}
+
+impl HingeLoss {
+    pub fn new(margin: f64, reduction: Reduction) -> Self {
+        Self { margin, reduction }
+    }
+}
+
+impl Default for HingeLoss {
+    fn default() -> Self {
+        Self::new(1.0, Reduction::default())
+    }
+}
+
+impl Loss for HingeLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        elementwise(prediction, target, |p, t| {
+            (Value::from(self.margin) - p * t).relu()
+        })
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+/// Binary cross-entropy of logits against targets of 0 or 1
+///
+/// Computed as `max(x, 0) - x * t + ln(1 + exp(-|x|))` so that large logits
+/// do not overflow
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BceWithLogitsLoss {
+    reduction: Reduction,
+}
+
+impl BceWithLogitsLoss {
+    pub fn new(reduction: Reduction) -> Self {
+        Self { reduction }
+    }
+}
+
+impl Loss for BceWithLogitsLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        elementwise(prediction, target, |x, t| {
+            x.clone().relu() - x.clone() * t + ((-x.abs()).exp() + 1.0).ln()
+        })
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+/// Categorical cross-entropy of a set of logits against a target distribution
+///
+/// The target holds the probability of each class, e.g. one-hot encoded labels.
+/// A prediction has a single term, `-sum(target * log_softmax(prediction))`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CrossEntropyLoss {
+    reduction: Reduction,
+}
+
+impl CrossEntropyLoss {
+    pub fn new(reduction: Reduction) -> Self {
+        Self { reduction }
+    }
+}
+
+impl Loss for CrossEntropyLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        assert_eq!(
+            prediction.len(),
+            target.len(),
+            "Prediction and target have different lengths"
+        );
+        vec![-Value::dot(target, &log_softmax(prediction))]
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+/// Negative log likelihood of the target under a Gaussian predicted by the model
+///
+/// The prediction holds the means followed by the log variances, so an
+/// `Mlp<I, 2>` predicts a single Gaussian. Each term is
+/// `0.5 * (log_var + (target - mean)^2 / exp(log_var))`, plus `0.5 * ln(2 pi)`
+/// when `full` is set.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GaussianNllLoss {
+    full: bool,
+    reduction: Reduction,
+}
+
+impl GaussianNllLoss {
+    pub fn new(full: bool, reduction: Reduction) -> Self {
+        Self { full, reduction }
+    }
+}
+
+impl Loss for GaussianNllLoss {
+    fn terms(&self, prediction: &[Value], target: &[Value]) -> Vec<Value> {
+        assert_eq!(
+            prediction.len(),
+            2 * target.len(),
+            "Prediction must have a mean and log variance for each target"
+        );
+        let (mean, log_var) = prediction.split_at(target.len());
+        mean.iter()
+            .zip(log_var)
+            .zip(target)
+            .map(|((m, lv), t)| {
+                let d = t.clone() - m.clone();
+                let nll = (lv.clone() + d.clone() * d / lv.clone().exp()) * 0.5;
+                if self.full {
+                    nll + 0.5 * (2.0 * PI).ln()
+                } else {
+                    nll
+                }
+            })
+            .collect()
+    }
+
+    fn reduction(&self) -> Reduction {
+        self.reduction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BceWithLogitsLoss, CrossEntropyLoss, GaussianNllLoss, HingeLoss, HuberLoss, Loss, MaeLoss,
+        MseLoss, Reduction,
+    };
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+    use rstest::rstest;
+
+    fn values(data: &[f64]) -> Vec<Value> {
+        data.iter().copied().map(Value::from).collect()
+    }
+
+    /// Compare the gradient of the loss for each prediction against central differences
+    fn check_gradient(loss: &dyn Loss, prediction: &[f64], target: &[f64]) {
+        let p = values(prediction);
+        let l = loss.loss(&p, &values(target));
+        l.backward();
+        let h = 1e-6;
+        for v in p.iter() {
+            let x = v.data();
+            v.set_data(x + h);
+            let up = l.recompute();
+            v.set_data(x - h);
+            let down = l.recompute();
+            v.set_data(x);
+            assert_abs_diff_eq!(v.gradient(), (up - down) / (2.0 * h), epsilon = 1e-6);
+        }
+    }
+
+    #[rstest]
+    #[case::mse(&MseLoss::default(), &[0.5, -1.0, 2.0], &[1.0, -1.0, 0.0])]
+    #[case::mae(&MaeLoss::new(Reduction::Sum), &[0.5, -1.5, 2.0], &[1.0, -1.0, 0.0])]
+    #[case::huber(&HuberLoss::default(), &[0.5, -1.5, 3.0], &[1.0, -1.0, 0.0])]
+    #[case::hinge(&HingeLoss::default(), &[0.5, -1.5, 0.2], &[1.0, -1.0, -1.0])]
+    #[case::bce(&BceWithLogitsLoss::default(), &[0.5, -30.0, 30.0], &[1.0, 0.0, 1.0])]
+    #[case::cross_entropy(&CrossEntropyLoss::default(), &[0.5, -1.5, 3.0], &[0.0, 1.0, 0.0])]
+    #[case::gaussian_nll(&GaussianNllLoss::new(true, Reduction::Mean), &[0.5, -1.0, 0.3, -2.0], &[1.0, 0.0])]
+    fn test_loss_gradient(
+        #[case] loss: &dyn Loss,
+        #[case] prediction: &[f64],
+        #[case] target: &[f64],
+    ) {
+        check_gradient(loss, prediction, target)
+    }
+
+    #[test]
+    fn test_reduction() {
+        let p = values(&[0.0, 1.0, 3.0]);
+        let t = values(&[1.0, 1.0, 1.0]);
+        assert_eq!(MseLoss::new(Reduction::Mean).loss(&p, &t).data(), 5.0 / 3.0);
+        assert_eq!(MseLoss::new(Reduction::Sum).loss(&p, &t).data(), 5.0);
+        let terms: Vec<f64> = MseLoss::new(Reduction::None)
+            .forward(&p, &t)
+            .iter()
+            .map(|v| v.data())
+            .collect();
+        assert_eq!(terms, vec![1.0, 0.0, 4.0]);
+        assert_eq!(MseLoss::new(Reduction::Mean).loss(&[], &[]).data(), 0.0);
+    }
+
+    #[test]
+    fn test_huber_recompute() {
+        let p = values(&[0.5]);
+        let loss = HuberLoss::new(2.0, Reduction::Sum).loss(&p, &values(&[0.0]));
+        assert_eq!(loss.data(), 0.125);
+        // Crossing delta switches from the squared to the absolute branch
+        p[0].set_data(-5.0);
+        assert_eq!(loss.recompute(), 8.0);
+        p[0].set_data(1.0);
+        assert_eq!(loss.recompute(), 0.5);
+    }
+
+    #[test]
+    fn test_loss_values() {
+        let t = values(&[0.0]);
+        assert_eq!(MaeLoss::default().loss(&values(&[-2.5]), &t).data(), 2.5);
+        assert_eq!(HuberLoss::default().loss(&values(&[0.5]), &t).data(), 0.125);
+        assert_eq!(HuberLoss::default().loss(&values(&[-3.0]), &t).data(), 2.5);
+        assert_eq!(
+            HingeLoss::default()
+                .loss(&values(&[0.25]), &values(&[1.0]))
+                .data(),
+            0.75
+        );
+
+        // Extreme logits stay finite
+        let bce = BceWithLogitsLoss::default().loss(&values(&[-1000.0]), &values(&[1.0]));
+        assert_eq!(bce.data(), 1000.0);
+        let bce = BceWithLogitsLoss::default().loss(&values(&[0.0]), &values(&[1.0]));
+        assert_abs_diff_eq!(bce.data(), 2.0_f64.ln(), epsilon = 1e-12);
+
+        let ce = CrossEntropyLoss::default().loss(&values(&[1000.0, 0.0]), &values(&[0.0, 1.0]));
+        assert_abs_diff_eq!(ce.data(), 1000.0, epsilon = 1e-9);
+
+        let nll = GaussianNllLoss::default().loss(&values(&[1.0, 0.0]), &values(&[3.0]));
+        assert_eq!(nll.data(), 2.0);
+    }
+}
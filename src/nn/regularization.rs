@@ -0,0 +1,141 @@
+use std::fmt::Debug;
+
+use crate::value::Value;
+
+/// A penalty on the size of a set of weights, added to the loss
+pub trait Regularizer: Debug {
+    fn penalty(&self, weights: &[Value]) -> Value;
+}
+
+/// A restriction on the weights of a neuron, enforced after each update
+pub trait Constraint: Debug {
+    fn apply(&self, weights: &[Value]);
+}
+
+/// Sum of absolute values, `strength * sum(|w|)`
+#[derive(Debug, Clone, Copy)]
+pub struct L1 {
+    strength: f64,
+}
+
+impl L1 {
+    pub fn new(strength: f64) -> Self {
+        Self { strength }
+    }
+}
+
+impl Regularizer for L1 {
+    fn penalty(&self, weights: &[Value]) -> Value {
+        weights.iter().map(|w| w.clone().abs()).sum::<Value>() * self.strength
+    }
+}
+
+/// Sum of squares, `strength * sum(w^2)`, computed as a single dot product
+#[derive(Debug, Clone, Copy)]
+pub struct L2 {
+    strength: f64,
+}
+
+impl L2 {
+    pub fn new(strength: f64) -> Self {
+        Self { strength }
+    }
+}
+
+impl Regularizer for L2 {
+    fn penalty(&self, weights: &[Value]) -> Value {
+        Value::dot(weights, weights) * self.strength
+    }
+}
+
+/// Combination of the L1 and L2 penalties
+#[derive(Debug, Clone, Copy)]
+pub struct ElasticNet {
+    l1: L1,
+    l2: L2,
+}
+
+impl ElasticNet {
+    pub fn new(l1: f64, l2: f64) -> Self {
+        Self {
+            l1: L1::new(l1),
+            l2: L2::new(l2),
+        }
+    }
+}
+
+impl Regularizer for ElasticNet {
+    fn penalty(&self, weights: &[Value]) -> Value {
+        self.l1.penalty(weights) + self.l2.penalty(weights)
+    }
+}
+
+/// Rescale the incoming weights of a neuron whenever their L2 norm exceeds `max_norm`
+#[derive(Debug, Clone, Copy)]
+pub struct MaxNorm {
+    max_norm: f64,
+}
+
+impl MaxNorm {
+    pub fn new(max_norm: f64) -> Self {
+        Self { max_norm }
+    }
+}
+
+impl Constraint for MaxNorm {
+    fn apply(&self, weights: &[Value]) {
+        let norm = weights.iter().map(|w| w.data().powi(2)).sum::<f64>().sqrt();
+        if norm > self.max_norm {
+            let scale = self.max_norm / norm;
+            for w in weights {
+                w.set_data(w.data() * scale)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Constraint, ElasticNet, MaxNorm, Regularizer, L1, L2};
+    use crate::value::{Operation, Value};
+    use approx::assert_abs_diff_eq;
+
+    fn weights(data: &[f64]) -> Vec<Value> {
+        data.iter().copied().map(Value::from).collect()
+    }
+
+    #[test]
+    fn test_penalties() {
+        let w = weights(&[1.0, -2.0, 0.5]);
+        let l1 = L1::new(0.1).penalty(&w);
+        assert_abs_diff_eq!(l1.data(), 0.35, epsilon = 1e-12);
+        let l2 = L2::new(0.1).penalty(&w);
+        assert_abs_diff_eq!(l2.data(), 0.525, epsilon = 1e-12);
+        let elastic = ElasticNet::new(0.1, 0.1).penalty(&w);
+        assert_abs_diff_eq!(elastic.data(), 0.875, epsilon = 1e-12);
+
+        elastic.backward();
+        let gradients: Vec<f64> = w.iter().map(|w| w.gradient()).collect();
+        for (g, e) in gradients.iter().zip([0.3, -0.5, 0.2]) {
+            assert_abs_diff_eq!(*g, e, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_l2_is_single_dot() {
+        let w = weights(&[1.0; 16]);
+        let penalty = L2::new(1.0).penalty(&w);
+        assert_eq!(penalty.children()[0].operation(), Some(Operation::Dot));
+        assert_eq!(penalty.topological_order().len(), 16 + 3);
+    }
+
+    #[test]
+    fn test_max_norm() {
+        let w = weights(&[3.0, 4.0]);
+        MaxNorm::new(10.0).apply(&w);
+        assert_eq!(w[0].data(), 3.0);
+        MaxNorm::new(1.0).apply(&w);
+        assert_abs_diff_eq!(w[0].data(), 0.6, epsilon = 1e-12);
+        assert_abs_diff_eq!(w[1].data(), 0.8, epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::value::Value;
+
+/// Leading bytes of the binary format, followed by a version
+const MAGIC: &[u8; 4] = b"UGSD";
+const VERSION: u8 = 1;
+
+/// The data of a named parameter, in row-major order
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f64>,
+}
+
+/// Number of values in a tensor of the given shape, or `None` on overflow
+pub(crate) fn size(shape: &[usize]) -> Option<usize> {
+    shape.iter().try_fold(1usize, |n, d| n.checked_mul(*d))
+}
+
+impl Tensor {
+    /// Panics if the number of values does not match the shape
+    pub fn new(shape: Vec<usize>, data: Vec<f64>) -> Self {
+        assert_eq!(
+            size(&shape),
+            Some(data.len()),
+            "Tensor data does not match its shape"
+        );
+        Self { shape, data }
+    }
+
+    fn is_valid(&self) -> bool {
+        size(&self.shape) == Some(self.data.len())
+    }
+}
+
+/// Error raised when saving, reading or loading a StateDict
+#[derive(Debug)]
+pub enum StateError {
+    Io(std::io::Error),
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+    #[cfg(feature = "npz")]
+    Zip(zip::result::ZipError),
+    /// A parameter of the model has no entry
+    Missing(String),
+    /// An entry does not correspond to a parameter of the model
+    Unexpected(String),
+    /// An entry has a different shape than the parameter
+    Shape {
+        name: String,
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+    /// The data could not be read as a StateDict
+    Invalid(String),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::Io(e) => write!(f, "{e}"),
+            #[cfg(feature = "serde")]
+            StateError::Json(e) => write!(f, "{e}"),
+            #[cfg(feature = "npz")]
+            StateError::Zip(e) => write!(f, "{e}"),
+            StateError::Missing(name) => write!(f, "missing entry for {name}"),
+            StateError::Unexpected(name) => write!(f, "unexpected entry {name}"),
+            StateError::Shape {
+                name,
+                expected,
+                actual,
+            } => write!(f, "{name} has shape {actual:?}, expected {expected:?}"),
+            StateError::Invalid(reason) => write!(f, "invalid state dict: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<std::io::Error> for StateError {
+    fn from(value: std::io::Error) -> Self {
+        StateError::Io(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for StateError {
+    fn from(value: serde_json::Error) -> Self {
+        StateError::Json(value)
+    }
+}
+
+#[cfg(feature = "npz")]
+impl From<zip::result::ZipError> for StateError {
+    fn from(value: zip::result::ZipError) -> Self {
+        StateError::Zip(value)
+    }
+}
+
+/// A parameter group of a model, e.g. the weights of a layer
+pub(crate) struct Entry {
+    name: String,
+    shape: Vec<usize>,
+    values: Vec<Value>,
+}
+
+impl Entry {
+    pub(crate) fn new(name: &str, shape: Vec<usize>, values: Vec<Value>) -> Self {
+        Self {
+            name: name.to_string(),
+            shape,
+            values,
+        }
+    }
+
+    /// Prepend a scope to the name, e.g. `weight` to `layer0.weight`
+    pub(crate) fn scoped(mut self, scope: &str) -> Self {
+        self.name = format!("{scope}.{}", self.name);
+        self
+    }
+}
+
+/// Named snapshot of the parameters of a model
+///
+/// Layers store their weights as `weight`, with shape `[outputs, inputs]`, and
+/// their biases as `bias`, with shape `[outputs]`. Layers of an Mlp are
+/// scoped by position, e.g. `layer1.weight`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct StateDict {
+    tensors: BTreeMap<String, Tensor>,
+}
+
+impl StateDict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn from_entries(entries: &[Entry]) -> Self {
+        Self {
+            tensors: entries
+                .iter()
+                .map(|e| {
+                    let data = e.values.iter().map(|v| v.data()).collect();
+                    (e.name.clone(), Tensor::new(e.shape.clone(), data))
+                })
+                .collect(),
+        }
+    }
+
+    /// Copy the data into the parameters of each entry
+    ///
+    /// Every entry is validated before any parameter is modified, so a failed
+    /// load leaves the model unchanged.
+    pub(crate) fn load_entries(&self, entries: &[Entry]) -> Result<(), StateError> {
+        for e in entries {
+            let tensor = self
+                .get(&e.name)
+                .ok_or_else(|| StateError::Missing(e.name.clone()))?;
+            if tensor.shape != e.shape || !tensor.is_valid() {
+                return Err(StateError::Shape {
+                    name: e.name.clone(),
+                    expected: e.shape.clone(),
+                    actual: tensor.shape.clone(),
+                });
+            }
+        }
+        if let Some(name) = self
+            .names()
+            .find(|n| !entries.iter().any(|e| &e.name == *n))
+        {
+            return Err(StateError::Unexpected(name.clone()));
+        }
+        for e in entries {
+            for (v, x) in e.values.iter().zip(&self.tensors[&e.name].data) {
+                v.set_data(*x)
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Tensor> {
+        self.tensors.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, tensor: Tensor) -> Option<Tensor> {
+        self.tensors.insert(name.to_string(), tensor)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Tensor> {
+        self.tensors.remove(name)
+    }
+
+    /// Entries within a scope, with the scope removed from their names, e.g.
+    /// `layer0.weight` becomes `weight` in scope `layer0`
+    pub fn scope(&self, scope: &str) -> StateDict {
+        let prefix = format!("{scope}.");
+        Self {
+            tensors: self
+                .iter()
+                .filter_map(|(name, t)| Some((name.strip_prefix(&prefix)?.to_string(), t.clone())))
+                .collect(),
+        }
+    }
+
+    /// Names of the entries, in sorted order
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.tensors.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Tensor)> {
+        self.tensors.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tensors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tensors.is_empty()
+    }
+
+    /// Compact binary encoding, which preserves every value exactly
+    ///
+    /// The magic bytes `UGSD` and a version byte are followed by the number of
+    /// entries and then, for each entry, its name, shape and data. Lengths and
+    /// dimensions are little-endian `u64`, and data is little-endian `f64`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        write_usize(&mut bytes, self.len());
+        for (name, tensor) in self.iter() {
+            write_usize(&mut bytes, name.len());
+            bytes.extend(name.as_bytes());
+            write_usize(&mut bytes, tensor.shape.len());
+            for d in &tensor.shape {
+                write_usize(&mut bytes, *d);
+            }
+            for x in &tensor.data {
+                bytes.extend(x.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Read the binary encoding created by `to_bytes`
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, StateError> {
+        let mut header = [0; 5];
+        read(&mut bytes, &mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(StateError::Invalid("unrecognized format".to_string()));
+        }
+        if header[4] != VERSION {
+            return Err(StateError::Invalid(format!(
+                "unsupported version {}",
+                header[4]
+            )));
+        }
+        let mut state = Self::new();
+        for _ in 0..read_usize(&mut bytes)? {
+            let mut name = vec![0; read_usize(&mut bytes)?.min(bytes.len())];
+            read(&mut bytes, &mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| StateError::Invalid("name is not UTF-8".to_string()))?;
+            let shape = (0..read_usize(&mut bytes)?)
+                .map(|_| read_usize(&mut bytes))
+                .collect::<Result<Vec<usize>, StateError>>()?;
+            let size = size(&shape)
+                .filter(|n| n.saturating_mul(8) <= bytes.len())
+                .ok_or_else(|| StateError::Invalid(format!("{name} is truncated")))?;
+            let data = (0..size)
+                .map(|_| {
+                    let mut x = [0; 8];
+                    read(&mut bytes, &mut x)?;
+                    Ok(f64::from_le_bytes(x))
+                })
+                .collect::<Result<Vec<f64>, StateError>>()?;
+            state.insert(&name, Tensor::new(shape, data));
+        }
+        if !bytes.is_empty() {
+            return Err(StateError::Invalid("trailing data".to_string()));
+        }
+        Ok(state)
+    }
+
+    /// Write the binary encoding to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StateError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&self.to_bytes())?;
+        Ok(writer.flush()?)
+    }
+
+    /// Read a file written by `save`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StateError> {
+        let mut bytes = Vec::new();
+        BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+fn write_usize(bytes: &mut Vec<u8>, n: usize) {
+    bytes.extend((n as u64).to_le_bytes())
+}
+
+fn read(bytes: &mut &[u8], buffer: &mut [u8]) -> Result<(), StateError> {
+    bytes
+        .read_exact(buffer)
+        .map_err(|_| StateError::Invalid("unexpected end of data".to_string()))
+}
+
+fn read_usize(bytes: &mut &[u8]) -> Result<usize, StateError> {
+    let mut n = [0; 8];
+    read(bytes, &mut n)?;
+    usize::try_from(u64::from_le_bytes(n))
+        .map_err(|_| StateError::Invalid("length overflows usize".to_string()))
+}
+
+/// JSON cannot represent NaN or infinity, so non-finite parameters should be
+/// saved in the binary format instead
+#[cfg(feature = "serde")]
+impl StateDict {
+    pub fn to_json(&self) -> Result<String, StateError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, StateError> {
+        let state: Self = serde_json::from_str(json)?;
+        if let Some((name, _)) = state.iter().find(|(_, t)| !t.is_valid()) {
+            return Err(StateError::Invalid(format!(
+                "{name} data does not match its shape"
+            )));
+        }
+        Ok(state)
+    }
+
+    /// Write the entries to a JSON file
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<(), StateError> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    /// Read a JSON file written by `save_json`
+    pub fn load_json(path: impl AsRef<Path>) -> Result<Self, StateError> {
+        let mut json = String::new();
+        BufReader::new(File::open(path)?).read_to_string(&mut json)?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateDict, StateError, Tensor};
+    use rstest::{fixture, rstest};
+
+    #[fixture]
+    fn state() -> StateDict {
+        let mut state = StateDict::new();
+        state.insert(
+            "layer0.weight",
+            Tensor::new(vec![2, 3], vec![0.1, -0.2, 1e-300, f64::MAX, -0.0, 3.0]),
+        );
+        state.insert("layer0.bias", Tensor::new(vec![2], vec![0.5, -1.5]));
+        state.insert("scalar", Tensor::new(vec![], vec![7.0]));
+        state
+    }
+
+    #[rstest]
+    fn test_binary_round_trip(state: StateDict) {
+        let bytes = state.to_bytes();
+        assert_eq!(&bytes[..4], b"UGSD");
+        assert_eq!(StateDict::from_bytes(&bytes).unwrap(), state);
+
+        // Non-finite values are preserved
+        let mut state = state;
+        state.insert("nan", Tensor::new(vec![2], vec![f64::NAN, f64::INFINITY]));
+        let restored = StateDict::from_bytes(&state.to_bytes()).unwrap();
+        let data = &restored.get("nan").unwrap().data;
+        assert!(data[0].is_nan() && data[1] == f64::INFINITY);
+    }
+
+    #[rstest]
+    fn test_file_round_trip(state: StateDict) {
+        let path = std::env::temp_dir().join(format!("ugradrs-{}.bin", std::process::id()));
+        state.save(&path).unwrap();
+        let restored = StateDict::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.unwrap(), state);
+        assert!(matches!(StateDict::load(&path), Err(StateError::Io(_))));
+    }
+
+    #[rstest]
+    fn test_invalid_bytes(state: StateDict) {
+        let bytes = state.to_bytes();
+        for end in [0, 3, 5, 20, bytes.len() - 1] {
+            assert!(matches!(
+                StateDict::from_bytes(&bytes[..end]),
+                Err(StateError::Invalid(_))
+            ));
+        }
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(StateDict::from_bytes(&extra).is_err());
+        let mut version = bytes;
+        version[4] = 2;
+        assert_eq!(
+            StateDict::from_bytes(&version).unwrap_err().to_string(),
+            "invalid state dict: unsupported version 2"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn test_json_round_trip(state: StateDict) {
+        let json = state.to_json().unwrap();
+        assert!(json.starts_with(r#"{"layer0.bias":{"shape":[2],"data":[0.5,-1.5]}"#));
+        assert_eq!(StateDict::from_json(&json).unwrap(), state);
+        assert!(StateDict::from_json(r#"{"w":{"shape":[2],"data":[1.0]}}"#).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::value::Value;
+
+/// Global L2 norm of the gradients of a set of parameters
+pub fn grad_norm(parameters: &[Value]) -> f64 {
+    parameters
+        .iter()
+        .map(|p| p.gradient().powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Scale the gradients of a set of parameters, such as those returned by
+/// `Mlp::parameters`, so that their global L2 norm is at most `max_norm`
+///
+/// Returns the norm before clipping. A non-finite norm is returned without
+/// modifying the gradients. Panics if `max_norm` is negative or NaN.
+pub fn clip_grad_norm(parameters: &[Value], max_norm: f64) -> f64 {
+    assert!(max_norm >= 0., "Maximum norm must not be negative or NaN");
+    let norm = grad_norm(parameters);
+    if norm.is_finite() && norm > max_norm {
+        let scale = max_norm / norm;
+        for p in parameters {
+            p.borrow_mut().gradient *= scale;
+        }
+    }
+    norm
+}
+
+/// Clamp the gradient of each parameter to `[-limit, limit]`
+///
+/// Returns the global L2 norm before clipping. Panics if `limit` is negative
+/// or NaN.
+pub fn clip_grad_value(parameters: &[Value], limit: f64) -> f64 {
+    assert!(limit >= 0., "Limit must not be negative or NaN");
+    let norm = grad_norm(parameters);
+    for p in parameters {
+        let mut internal = p.borrow_mut();
+        internal.gradient = internal.gradient.clamp(-limit, limit);
+    }
+    norm
+}
+
+/// Label and absolute gradient of each parameter, largest first
+pub fn parameter_grad_norms(parameters: &[Value]) -> Vec<(Option<String>, f64)> {
+    let mut norms: Vec<_> = parameters
+        .iter()
+        .map(|p| (p.label(), p.gradient().abs()))
+        .collect();
+    norms.sort_by(|a, b| b.1.total_cmp(&a.1));
+    norms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clip_grad_norm, clip_grad_value, grad_norm, parameter_grad_norms};
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+    use rstest::rstest;
+
+    fn parameters(gradients: &[f64]) -> Vec<Value> {
+        gradients
+            .iter()
+            .enumerate()
+            .map(|(i, g)| {
+                let p = Value::named(&format!("p{i}"), 0.0);
+                p.borrow_mut().gradient = *g;
+                p
+            })
+            .collect()
+    }
+
+    fn gradients(parameters: &[Value]) -> Vec<f64> {
+        parameters.iter().map(|p| p.gradient()).collect()
+    }
+
+    #[test]
+    fn test_clip_grad_norm() {
+        let p = parameters(&[3.0, -4.0]);
+        assert_eq!(grad_norm(&p), 5.0);
+        assert_eq!(clip_grad_norm(&p, 10.0), 5.0);
+        assert_eq!(gradients(&p), vec![3.0, -4.0]);
+        assert_eq!(clip_grad_norm(&p, 1.0), 5.0);
+        assert_abs_diff_eq!(grad_norm(&p), 1.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(p[0].gradient(), 0.6, epsilon = 1e-12);
+
+        let p = parameters(&[f64::NAN, 1.0]);
+        assert!(clip_grad_norm(&p, 1.0).is_nan());
+        assert_eq!(p[1].gradient(), 1.0);
+    }
+
+    #[test]
+    fn test_clip_grad_value() {
+        let p = parameters(&[3.0, -4.0, 0.5]);
+        assert_eq!(clip_grad_value(&p, 1.0), 25.25_f64.sqrt());
+        assert_eq!(gradients(&p), vec![1.0, -1.0, 0.5]);
+    }
+
+    #[rstest]
+    #[should_panic(expected = "must not be negative or NaN")]
+    fn test_invalid_limit(
+        #[values(-1.0, f64::NAN)] limit: f64,
+        #[values(clip_grad_norm, clip_grad_value)] clip: fn(&[Value], f64) -> f64,
+    ) {
+        clip(&parameters(&[3.0, -4.0]), limit);
+    }
+
+    #[test]
+    fn test_parameter_grad_norms() {
+        let p = parameters(&[0.5, -4.0, 2.0]);
+        assert_eq!(
+            parameter_grad_norms(&p),
+            vec![
+                (Some("p1".to_string()), 4.0),
+                (Some("p2".to_string()), 2.0),
+                (Some("p0".to_string()), 0.5),
+            ]
+        );
+    }
+}
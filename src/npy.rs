@@ -0,0 +1,418 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::data::Samples;
+#[cfg(feature = "npz")]
+use crate::nn::state::StateDict;
+use crate::nn::state::{size, StateError, Tensor};
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+fn invalid(reason: impl Into<String>) -> StateError {
+    StateError::Invalid(reason.into())
+}
+
+/// Raw value of a key in the header dictionary, e.g. `(2, 3)` for `'shape'`
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, StateError> {
+    let start = header
+        .find(&format!("'{key}':"))
+        .ok_or_else(|| invalid(format!("header is missing {key}")))?
+        + key.len()
+        + 3;
+    let rest = header[start..].trim_start();
+    let end = match rest.chars().next() {
+        Some('\'') => rest[1..].find('\'').map(|i| i + 2),
+        Some('(') => rest.find(')').map(|i| i + 1),
+        _ => rest.find([',', '}']),
+    };
+    Ok(rest[..end.ok_or_else(|| invalid(format!("malformed {key}")))?].trim())
+}
+
+/// Decode the values of an array with a NumPy type string such as `<f8` or `|u1`
+fn decode(descr: &str, bytes: &[u8], count: usize) -> Result<Vec<f64>, StateError> {
+    let unsupported = || invalid(format!("unsupported dtype {descr}"));
+    let mut chars = descr.chars();
+    let big_endian = match chars.next() {
+        Some('<' | '|' | '=') => false,
+        Some('>') => true,
+        _ => return Err(unsupported()),
+    };
+    let kind = chars.next().ok_or_else(unsupported)?;
+    let size: usize = chars.as_str().parse().map_err(|_| unsupported())?;
+    if !matches!(
+        (kind, size),
+        ('f', 4 | 8) | ('i' | 'u', 1 | 2 | 4 | 8) | ('b', 1)
+    ) {
+        return Err(unsupported());
+    }
+    if count.checked_mul(size) != Some(bytes.len()) {
+        return Err(invalid("array data does not match its shape"));
+    }
+    Ok(bytes
+        .chunks_exact(size)
+        .map(|chunk| {
+            let mut b = [0; 8];
+            b[..size].copy_from_slice(chunk);
+            if big_endian {
+                b[..size].reverse();
+            }
+            match (kind, size) {
+                ('f', 4) => f32::from_le_bytes(b[..4].try_into().unwrap()) as f64,
+                ('f', 8) => f64::from_le_bytes(b),
+                ('i', 1) => b[0] as i8 as f64,
+                ('i', 2) => i16::from_le_bytes([b[0], b[1]]) as f64,
+                ('i', 4) => i32::from_le_bytes(b[..4].try_into().unwrap()) as f64,
+                ('i', 8) => i64::from_le_bytes(b) as f64,
+                ('u' | 'b', 1) => b[0] as f64,
+                ('u', 2) => u16::from_le_bytes([b[0], b[1]]) as f64,
+                ('u', 4) => u32::from_le_bytes(b[..4].try_into().unwrap()) as f64,
+                _ => u64::from_le_bytes(b) as f64,
+            }
+        })
+        .collect())
+}
+
+/// Reorder column-major data into row-major order
+fn from_fortran_order(shape: &[usize], data: Vec<f64>) -> Vec<f64> {
+    (0..data.len())
+        .map(|mut index| {
+            // Decompose the row-major index, accumulating the column-major offset
+            let mut offset = 0;
+            let mut stride = data.len();
+            for d in shape.iter().rev() {
+                stride /= d;
+                offset += (index % d) * stride;
+                index /= d;
+            }
+            data[offset]
+        })
+        .collect()
+}
+
+/// Read an array in the NumPy `.npy` format
+///
+/// Floating point, integer and boolean arrays of either byte order are
+/// converted to `f64`, and Fortran ordered arrays to row-major order.
+pub fn read_npy(mut reader: impl Read) -> Result<Tensor, StateError> {
+    let mut preamble = [0; 8];
+    reader.read_exact(&mut preamble)?;
+    if &preamble[..6] != MAGIC {
+        return Err(invalid("not an npy file"));
+    }
+    let header_len = match preamble[6] {
+        1 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0; 4];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        v => return Err(invalid(format!("unsupported npy version {v}"))),
+    };
+    let mut header = vec![0; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|_| invalid("header is not UTF-8"))?;
+
+    let descr = header_value(&header, "descr")?.trim_matches('\'');
+    let fortran_order = match header_value(&header, "fortran_order")? {
+        "True" => true,
+        "False" => false,
+        other => return Err(invalid(format!("malformed fortran_order {other}"))),
+    };
+    let shape = header_value(&header, "shape")?
+        .trim_matches(['(', ')'])
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse()
+                .map_err(|_| invalid(format!("malformed shape {d}")))
+        })
+        .collect::<Result<Vec<usize>, StateError>>()?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let count = size(&shape).ok_or_else(|| invalid("shape is too large"))?;
+    let data = decode(descr, &bytes, count)?;
+    let data = if fortran_order && shape.len() > 1 {
+        from_fortran_order(&shape, data)
+    } else {
+        data
+    };
+    Ok(Tensor::new(shape, data))
+}
+
+/// Write an array in the NumPy `.npy` format, as little-endian `f64`
+pub fn write_npy(mut writer: impl Write, tensor: &Tensor) -> Result<(), StateError> {
+    let shape = match tensor.shape.as_slice() {
+        [d] => format!("({d},)"),
+        dims => format!(
+            "({})",
+            dims.iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {shape}, }}");
+    // Pad with spaces so that the data is aligned to 64 bytes
+    let padding = 63 - (MAGIC.len() + 4 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    let header_len =
+        u16::try_from(header.len()).map_err(|_| invalid("too many dimensions for npy"))?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&header_len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for x in &tensor.data {
+        writer.write_all(&x.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Read an `.npy` file
+pub fn load_npy(path: impl AsRef<Path>) -> Result<Tensor, StateError> {
+    read_npy(BufReader::new(File::open(path)?))
+}
+
+/// Write a tensor to an `.npy` file
+pub fn save_npy(path: impl AsRef<Path>, tensor: &Tensor) -> Result<(), StateError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_npy(&mut writer, tensor)?;
+    Ok(writer.flush()?)
+}
+
+/// Read every array of an `.npz` archive, as created by `numpy.savez` or
+/// `numpy.savez_compressed`, named without the `.npy` extension
+#[cfg(feature = "npz")]
+pub fn load_npz(path: impl AsRef<Path>) -> Result<StateDict, StateError> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+    let mut state = StateDict::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = file.name().trim_end_matches(".npy").to_string();
+        state.insert(&name, read_npy(file)?);
+    }
+    Ok(state)
+}
+
+/// Write every entry as an uncompressed array of an `.npz` archive, readable
+/// with `numpy.load`
+#[cfg(feature = "npz")]
+pub fn save_npz(path: impl AsRef<Path>, state: &StateDict) -> Result<(), StateError> {
+    let mut archive = zip::ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, tensor) in state.iter() {
+        archive.start_file(format!("{name}.npy"), options)?;
+        write_npy(&mut archive, tensor)?;
+    }
+    archive.finish()?.flush()?;
+    Ok(())
+}
+
+/// Rows of a 2D array with `N` columns, or of a 1D array when `N` is one
+fn rows<const N: usize>(tensor: &Tensor, name: &str) -> Result<Vec<[f64; N]>, StateError> {
+    match tensor.shape.as_slice() {
+        [_, n] if *n == N => {}
+        [_] if N == 1 => {}
+        _ => {
+            return Err(StateError::Shape {
+                name: name.to_string(),
+                expected: vec![tensor.shape.first().copied().unwrap_or(0), N],
+                actual: tensor.shape.clone(),
+            })
+        }
+    }
+    Ok(tensor
+        .data
+        .chunks_exact(N)
+        .map(|row| row.try_into().unwrap())
+        .collect())
+}
+
+/// Pair the rows of an input and target array into a Dataset
+///
+/// The inputs must have shape `[samples, I]` and the targets `[samples, O]`,
+/// with 1D arrays accepted for a single column.
+pub fn dataset<const I: usize, const O: usize>(
+    inputs: &Tensor,
+    targets: &Tensor,
+) -> Result<Samples<I, O>, StateError> {
+    let x = rows::<I>(inputs, "inputs")?;
+    let y = rows::<O>(targets, "targets")?;
+    if x.len() != y.len() {
+        let mut expected = targets.shape.clone();
+        expected[0] = x.len();
+        return Err(StateError::Shape {
+            name: "targets".to_string(),
+            expected,
+            actual: targets.shape.clone(),
+        });
+    }
+    Ok(x.into_iter().zip(y).collect())
+}
+
+/// Load a Dataset from `.npy` files of inputs and targets, see `dataset`
+pub fn load_dataset<const I: usize, const O: usize>(
+    inputs: impl AsRef<Path>,
+    targets: impl AsRef<Path>,
+) -> Result<Samples<I, O>, StateError> {
+    dataset(&load_npy(inputs)?, &load_npy(targets)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dataset, read_npy, write_npy};
+    use crate::nn::state::{StateError, Tensor};
+    use rstest::rstest;
+
+    /// An npy file as written by NumPy, with a version 1.0 header
+    fn npy(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = header.to_string();
+        header.push_str(&" ".repeat(63 - (10 + header.len()) % 64));
+        header.push('\n');
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tensor = Tensor::new(vec![2, 3], vec![0.1, -2.0, 1e-300, f64::MAX, f64::NAN, 3.0]);
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, &tensor).unwrap();
+        assert_eq!(bytes.len() % 64, 48);
+        assert!(bytes.starts_with(
+            b"\x93NUMPY\x01\x00v\x00{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }"
+        ));
+        let restored = read_npy(bytes.as_slice()).unwrap();
+        assert_eq!(restored.shape, tensor.shape);
+        assert_eq!(restored.data[..4], tensor.data[..4]);
+        assert!(restored.data[4].is_nan());
+
+        for shape in [vec![], vec![1]] {
+            let tensor = Tensor::new(shape, vec![4.0]);
+            let mut bytes = Vec::new();
+            write_npy(&mut bytes, &tensor).unwrap();
+            assert_eq!(read_npy(bytes.as_slice()).unwrap(), tensor);
+        }
+    }
+
+    #[rstest]
+    #[case::float32("<f4", [1.5_f32, -2.0].iter().flat_map(|x| x.to_le_bytes()).collect())]
+    #[case::big_endian(">f8", [1.5_f64, -2.0].iter().flat_map(|x| x.to_be_bytes()).collect())]
+    #[case::int64("<i8", [1_i64, -2].iter().flat_map(|x| x.to_le_bytes()).collect())]
+    #[case::int32(">i4", [1_i32, -2].iter().flat_map(|x| x.to_be_bytes()).collect())]
+    #[case::uint8("|u1", vec![1, 254])]
+    fn test_dtypes(#[case] descr: &str, #[case] data: Vec<u8>) {
+        let header = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': (2,), }}");
+        let tensor = read_npy(npy(&header, &data).as_slice()).unwrap();
+        let expected = if descr == "|u1" { 254. } else { -2. };
+        assert_eq!(tensor.data[1], expected);
+        assert_eq!(
+            tensor.data[0],
+            if descr == "<f4" || descr == ">f8" {
+                1.5
+            } else {
+                1.
+            }
+        );
+    }
+
+    #[test]
+    fn test_fortran_order() {
+        let data: Vec<u8> = [1., 4., 2., 5., 3., 6.]
+            .iter()
+            .flat_map(|x: &f64| x.to_le_bytes())
+            .collect();
+        let header = "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }";
+        let tensor = read_npy(npy(header, &data).as_slice()).unwrap();
+        assert_eq!(tensor.data, vec![1., 2., 3., 4., 5., 6.]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(read_npy(&b"not numpy"[..]).is_err());
+        let header = "{'descr': '<c16', 'fortran_order': False, 'shape': (1,), }";
+        assert_eq!(
+            read_npy(npy(header, &[0; 16]).as_slice())
+                .unwrap_err()
+                .to_string(),
+            "invalid state dict: unsupported dtype <c16"
+        );
+        let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }";
+        assert!(matches!(
+            read_npy(npy(header, &[0; 8]).as_slice()),
+            Err(StateError::Invalid(_))
+        ));
+    }
+
+    #[rstest]
+    #[case::shape("(4294967296, 4294967296, 4294967296)", "shape is too large")]
+    #[case::bytes("(2305843009213693952,)", "array data does not match its shape")]
+    fn test_overflow(#[case] shape: &str, #[case] message: &str) {
+        let header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {shape}, }}");
+        assert_eq!(
+            read_npy(npy(&header, &[0; 8]).as_slice())
+                .unwrap_err()
+                .to_string(),
+            format!("invalid state dict: {message}")
+        );
+    }
+
+    #[test]
+    fn test_dataset() {
+        let inputs = Tensor::new(vec![3, 2], vec![1., 2., 3., 4., 5., 6.]);
+        let targets = Tensor::new(vec![3], vec![-1., 1., -1.]);
+        let data = dataset::<2, 1>(&inputs, &targets).unwrap();
+        assert_eq!(data[1], ([3., 4.], [1.]));
+
+        assert!(matches!(
+            dataset::<3, 1>(&inputs, &targets),
+            Err(StateError::Shape { .. })
+        ));
+        let targets = Tensor::new(vec![2, 1], vec![-1., 1.]);
+        assert!(matches!(
+            dataset::<2, 1>(&inputs, &targets),
+            Err(StateError::Shape { expected, actual, .. })
+                if expected == vec![3, 1] && actual == vec![2, 1]
+        ));
+        let targets = Tensor::new(vec![2], vec![-1., 1.]);
+        assert!(matches!(
+            dataset::<2, 1>(&inputs, &targets),
+            Err(StateError::Shape { expected, actual, .. })
+                if expected == vec![3] && actual == vec![2]
+        ));
+    }
+
+    #[cfg(feature = "npz")]
+    #[test]
+    fn test_npz_mlp() {
+        use super::{load_npz, save_npz};
+        use crate::nn::activation::Activation;
+        use crate::nn::{Layer, Mlp, SizedLayer};
+
+        let mlp: Mlp<2, 1> = Mlp::from_layer(SizedLayer::<2, 3>::new(Activation::Tanh))
+            .add_layer(SizedLayer::new(Activation::Identity));
+        let path = std::env::temp_dir().join(format!("ugradrs-{}.npz", std::process::id()));
+        save_npz(&path, &mlp.state_dict()).unwrap();
+        let state = load_npz(&path);
+        std::fs::remove_file(&path).unwrap();
+        let state = state.unwrap();
+        assert_eq!(state, mlp.state_dict());
+
+        // Layers can be imported individually
+        let layer = SizedLayer::<2, 3>::new(Activation::Tanh);
+        layer.load_state_dict(&state.scope("layer0")).unwrap();
+        assert_eq!(layer.state_dict(), mlp.layers()[0].state_dict());
+    }
+}
@@ -0,0 +1,656 @@
+use prost::Message;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::nn::activation::Activation;
+use crate::nn::dynamic::DynMlp;
+use crate::nn::{Layer, Mlp};
+
+use proto::{
+    dimension, type_proto, AttributeProto, Dimension, GraphProto, ModelProto, NodeProto,
+    OperatorSetIdProto, TensorProto, TensorShapeProto, TypeProto, ValueInfoProto,
+};
+
+/// Subset of the ONNX protobuf messages needed to describe an Mlp, with the
+/// field numbers of `onnx.proto`
+mod proto {
+    use prost::{Message, Oneof};
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct ModelProto {
+        #[prost(int64, tag = "1")]
+        pub ir_version: i64,
+        #[prost(string, tag = "2")]
+        pub producer_name: String,
+        #[prost(string, tag = "3")]
+        pub producer_version: String,
+        #[prost(message, optional, tag = "7")]
+        pub graph: Option<GraphProto>,
+        #[prost(message, repeated, tag = "8")]
+        pub opset_import: Vec<OperatorSetIdProto>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct OperatorSetIdProto {
+        #[prost(string, tag = "1")]
+        pub domain: String,
+        #[prost(int64, tag = "2")]
+        pub version: i64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct GraphProto {
+        #[prost(message, repeated, tag = "1")]
+        pub node: Vec<NodeProto>,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(message, repeated, tag = "5")]
+        pub initializer: Vec<TensorProto>,
+        #[prost(message, repeated, tag = "11")]
+        pub input: Vec<ValueInfoProto>,
+        #[prost(message, repeated, tag = "12")]
+        pub output: Vec<ValueInfoProto>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct NodeProto {
+        #[prost(string, repeated, tag = "1")]
+        pub input: Vec<String>,
+        #[prost(string, repeated, tag = "2")]
+        pub output: Vec<String>,
+        #[prost(string, tag = "3")]
+        pub name: String,
+        #[prost(string, tag = "4")]
+        pub op_type: String,
+        #[prost(message, repeated, tag = "5")]
+        pub attribute: Vec<AttributeProto>,
+    }
+
+    /// `onnx.proto` is proto2, so attribute values are optional to keep zero
+    /// values present on the wire
+    #[derive(Clone, PartialEq, Message)]
+    pub struct AttributeProto {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(int64, optional, tag = "3")]
+        pub i: Option<i64>,
+        #[prost(int32, tag = "20")]
+        pub r#type: i32,
+    }
+
+    /// `dims` is unpacked, as repeated scalars are by default in proto2
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TensorProto {
+        #[prost(int64, repeated, packed = "false", tag = "1")]
+        pub dims: Vec<i64>,
+        #[prost(int32, tag = "2")]
+        pub data_type: i32,
+        #[prost(string, tag = "8")]
+        pub name: String,
+        #[prost(bytes = "vec", tag = "9")]
+        pub raw_data: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct ValueInfoProto {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(message, optional, tag = "2")]
+        pub r#type: Option<TypeProto>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TypeProto {
+        #[prost(oneof = "type_proto::Value", tags = "1")]
+        pub value: Option<type_proto::Value>,
+    }
+
+    pub mod type_proto {
+        use super::{Message, Oneof, TensorShapeProto};
+
+        #[derive(Clone, PartialEq, Message)]
+        pub struct Tensor {
+            #[prost(int32, tag = "1")]
+            pub elem_type: i32,
+            #[prost(message, optional, tag = "2")]
+            pub shape: Option<TensorShapeProto>,
+        }
+
+        #[derive(Clone, PartialEq, Oneof)]
+        pub enum Value {
+            #[prost(message, tag = "1")]
+            TensorType(Tensor),
+        }
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TensorShapeProto {
+        #[prost(message, repeated, tag = "1")]
+        pub dim: Vec<Dimension>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Dimension {
+        #[prost(oneof = "dimension::Value", tags = "1, 2")]
+        pub value: Option<dimension::Value>,
+    }
+
+    pub mod dimension {
+        use super::Oneof;
+
+        #[derive(Clone, PartialEq, Oneof)]
+        pub enum Value {
+            #[prost(int64, tag = "1")]
+            DimValue(i64),
+            #[prost(string, tag = "2")]
+            DimParam(String),
+        }
+    }
+}
+
+/// Version of the default ONNX operator set the graph is written against
+const OPSET_VERSION: i64 = 13;
+/// ONNX IR version matching `OPSET_VERSION`
+const IR_VERSION: i64 = 7;
+/// `TensorProto.DataType.DOUBLE`, so the exported parameters are exact
+const DOUBLE: i32 = 11;
+const ATTRIBUTE_INT: i32 = 2;
+
+/// Error raised when exporting an Mlp to ONNX
+#[derive(Debug)]
+pub enum OnnxError {
+    Io(std::io::Error),
+    /// A layer cannot be described with standard ONNX operators
+    Unsupported(String),
+}
+
+impl Display for OnnxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnnxError::Io(e) => write!(f, "{e}"),
+            OnnxError::Unsupported(reason) => write!(f, "unsupported by ONNX export: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for OnnxError {}
+
+impl From<std::io::Error> for OnnxError {
+    fn from(value: std::io::Error) -> Self {
+        OnnxError::Io(value)
+    }
+}
+
+/// Graph input or output of doubles with a variable batch size
+fn value_info(name: &str, features: usize) -> ValueInfoProto {
+    let dim = |value| Dimension { value: Some(value) };
+    ValueInfoProto {
+        name: name.to_string(),
+        r#type: Some(TypeProto {
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: DOUBLE,
+                shape: Some(TensorShapeProto {
+                    dim: vec![
+                        dim(dimension::Value::DimParam("batch".to_string())),
+                        dim(dimension::Value::DimValue(features as i64)),
+                    ],
+                }),
+            })),
+        }),
+    }
+}
+
+/// Nodes and initializers of the graph as it is built
+#[derive(Default)]
+struct Builder {
+    nodes: Vec<NodeProto>,
+    initializers: Vec<TensorProto>,
+}
+
+impl Builder {
+    /// Add a node, returning the name of its output
+    fn node(
+        &mut self,
+        op_type: &str,
+        inputs: &[&str],
+        output: String,
+        attribute: Vec<AttributeProto>,
+    ) -> String {
+        self.nodes.push(NodeProto {
+            input: inputs.iter().map(|i| i.to_string()).collect(),
+            output: vec![output.clone()],
+            name: output.clone(),
+            op_type: op_type.to_string(),
+            attribute,
+        });
+        output
+    }
+
+    /// Add a constant tensor of doubles, returning its name
+    fn initializer(&mut self, name: String, dims: &[usize], data: &[f64]) -> String {
+        self.initializers.push(TensorProto {
+            dims: dims.iter().map(|d| *d as i64).collect(),
+            data_type: DOUBLE,
+            name: name.clone(),
+            raw_data: data.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        });
+        name
+    }
+
+    /// Add the nodes of an activation, returning the name of its output
+    fn activation(&mut self, activation: Activation, x: String) -> Result<String, OnnxError> {
+        let op_type = match activation {
+            Activation::Identity => return Ok(x),
+            Activation::Relu => "Relu",
+            Activation::Tanh => "Tanh",
+            Activation::Sigmoid => "Sigmoid",
+            Activation::Softplus => "Softplus",
+            Activation::LeakyRelu(slope) => return Ok(self.leaky_relu(x, slope)),
+            Activation::Gelu => return Ok(self.gelu(x)),
+            Activation::Custom(_) => {
+                return Err(OnnxError::Unsupported(
+                    "custom activation functions".to_string(),
+                ))
+            }
+        };
+        Ok(self.node(
+            op_type,
+            &[&x],
+            format!("{x}.{}", op_type.to_lowercase()),
+            vec![],
+        ))
+    }
+
+    /// LeakyRelu as `relu(x) - relu(-x) * slope`, like `Activation::apply`, as
+    /// the `alpha` attribute of the `LeakyRelu` operator is single precision
+    fn leaky_relu(&mut self, x: String, slope: f64) -> String {
+        let slope = self.initializer(format!("{x}.leaky_relu.slope"), &[], &[slope]);
+        let name = |step: &str| format!("{x}.leaky_relu.{step}");
+        let positive = self.node("Relu", &[&x], name("positive"), vec![]);
+        let negated = self.node("Neg", &[&x], name("negated"), vec![]);
+        let negative = self.node("Relu", &[&negated], name("negative"), vec![]);
+        let scaled = self.node("Mul", &[&negative, &slope], name("scaled"), vec![]);
+        self.node("Sub", &[&positive, &scaled], name("output"), vec![])
+    }
+
+    /// The tanh approximation of Gelu, built from elementwise operators as
+    /// `Gelu` was only added to the default operator set in version 20
+    fn gelu(&mut self, x: String) -> String {
+        let constant = |b: &mut Self, name: &str, value: f64| {
+            b.initializer(format!("{x}.gelu.{name}"), &[], &[value])
+        };
+        let cubic = constant(self, "cubic", 0.044715);
+        let scale = constant(self, "scale", (2. / std::f64::consts::PI).sqrt());
+        let one = constant(self, "one", 1.);
+        let half = constant(self, "half", 0.5);
+
+        let name = |step: &str| format!("{x}.gelu.{step}");
+        let square = self.node("Mul", &[&x, &x], name("square"), vec![]);
+        let cube = self.node("Mul", &[&square, &x], name("cube"), vec![]);
+        let scaled = self.node("Mul", &[&cube, &cubic], name("scaled_cube"), vec![]);
+        let sum = self.node("Add", &[&x, &scaled], name("sum"), vec![]);
+        let inner = self.node("Mul", &[&sum, &scale], name("inner"), vec![]);
+        let tanh = self.node("Tanh", &[&inner], name("tanh"), vec![]);
+        let shifted = self.node("Add", &[&tanh, &one], name("shifted"), vec![]);
+        let product = self.node("Mul", &[&x, &shifted], name("product"), vec![]);
+        self.node("Mul", &[&product, &half], name("output"), vec![])
+    }
+}
+
+/// Describe the layers as an ONNX model with a `[batch, inputs]` input named
+/// `input` and a `[batch, outputs]` output named `output`
+///
+/// Each layer is a `Gemm` of its weights, named as in the state dict, followed
+/// by the operators of its activation.
+fn model(inputs: usize, layers: &[Box<dyn Layer>]) -> Result<ModelProto, OnnxError> {
+    let mut builder = Builder::default();
+    let mut x = "input".to_string();
+    let mut outputs = inputs;
+    for (i, layer) in layers.iter().enumerate() {
+        let weights: Vec<f64> = layer.weights().concat().iter().map(|w| w.data()).collect();
+        let biases: Vec<f64> = layer.biases().iter().map(|b| b.data()).collect();
+        let weight = builder.initializer(
+            format!("layer{i}.weight"),
+            &[layer.outputs(), layer.inputs()],
+            &weights,
+        );
+        let bias = builder.initializer(format!("layer{i}.bias"), &[layer.outputs()], &biases);
+        let transpose = AttributeProto {
+            name: "transB".to_string(),
+            i: Some(1),
+            r#type: ATTRIBUTE_INT,
+        };
+        x = builder.node(
+            "Gemm",
+            &[&x, &weight, &bias],
+            format!("layer{i}"),
+            vec![transpose],
+        );
+        x = builder.activation(layer.activation(), x)?;
+        outputs = layer.outputs();
+    }
+    // Name the final value `output`, passing the input through for an empty Mlp
+    match builder.nodes.last_mut() {
+        Some(last) => last.output = vec!["output".to_string()],
+        None => {
+            builder.node("Identity", &[&x], "output".to_string(), vec![]);
+        }
+    }
+
+    Ok(ModelProto {
+        ir_version: IR_VERSION,
+        producer_name: env!("CARGO_PKG_NAME").to_string(),
+        producer_version: env!("CARGO_PKG_VERSION").to_string(),
+        graph: Some(GraphProto {
+            node: builder.nodes,
+            name: "mlp".to_string(),
+            initializer: builder.initializers,
+            input: vec![value_info("input", inputs)],
+            output: vec![value_info("output", outputs)],
+        }),
+        opset_import: vec![OperatorSetIdProto {
+            domain: String::new(),
+            version: OPSET_VERSION,
+        }],
+    })
+}
+
+fn save(bytes: Vec<u8>, path: impl AsRef<Path>) -> Result<(), OnnxError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&bytes)?;
+    Ok(writer.flush()?)
+}
+
+impl DynMlp {
+    /// Encode the Mlp as an ONNX model, see `Mlp::export_onnx`
+    pub fn to_onnx(&self) -> Result<Vec<u8>, OnnxError> {
+        Ok(model(self.inputs(), self.layers())?.encode_to_vec())
+    }
+
+    /// Write the Mlp to an `.onnx` file, see `Mlp::export_onnx`
+    pub fn export_onnx(&self, path: impl AsRef<Path>) -> Result<(), OnnxError> {
+        save(self.to_onnx()?, path)
+    }
+}
+
+impl<const I: usize, const O: usize> Mlp<I, O> {
+    /// Encode the Mlp as an ONNX model, see `export_onnx`
+    pub fn to_onnx(&self) -> Result<Vec<u8>, OnnxError> {
+        Ok(model(I, self.layers())?.encode_to_vec())
+    }
+
+    /// Write the Mlp to an `.onnx` file for standard inference runtimes
+    ///
+    /// The graph maps an `input` of shape `[batch, I]` to an `output` of shape
+    /// `[batch, O]`, with parameters stored as doubles. Each layer is a `Gemm`
+    /// followed by its activation. Custom activations cannot be exported.
+    pub fn export_onnx(&self, path: impl AsRef<Path>) -> Result<(), OnnxError> {
+        save(self.to_onnx()?, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::{dimension, type_proto, AttributeProto, ModelProto, NodeProto, TensorProto};
+    use super::{value_info, OnnxError};
+    use crate::nn::activation::Activation;
+    use crate::nn::dynamic::{DynLayer, DynMlp};
+    use crate::nn::{Mlp, SizedLayer};
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+    use prost::Message;
+    use rstest::rstest;
+    use std::collections::HashMap;
+
+    /// A tensor as its shape and row-major data
+    type Tensor = (Vec<usize>, Vec<f64>);
+
+    /// Evaluate a node of the exported graph
+    fn evaluate(node: &NodeProto, values: &HashMap<String, Tensor>) -> Tensor {
+        let (shape, x) = values[&node.input[0]].clone();
+        let attribute = |name: &str| node.attribute.iter().find(|a| a.name == name);
+        let map = |f: &dyn Fn(f64) -> f64| (shape.clone(), x.iter().map(|x| f(*x)).collect());
+        let binary = |f: &dyn Fn(f64, f64) -> f64| {
+            // The second operand is either the same shape or a scalar
+            let y = &values[&node.input[1]].1;
+            let data = x
+                .iter()
+                .enumerate()
+                .map(|(i, x)| f(*x, y[i % y.len()]))
+                .collect();
+            (shape.clone(), data)
+        };
+        match node.op_type.as_str() {
+            "Gemm" => {
+                assert_eq!(attribute("transB").unwrap().i, Some(1));
+                let (w_shape, w) = &values[&node.input[1]];
+                let b = &values[&node.input[2]].1;
+                let (batch, inputs, outputs) = (shape[0], shape[1], w_shape[0]);
+                assert_eq!(w_shape[1], inputs);
+                let data = (0..batch * outputs)
+                    .map(|k| {
+                        let (row, o) = (k / outputs, k % outputs);
+                        (0..inputs)
+                            .map(|i| x[row * inputs + i] * w[o * inputs + i])
+                            .sum::<f64>()
+                            + b[o]
+                    })
+                    .collect();
+                (vec![batch, outputs], data)
+            }
+            "Relu" => map(&|x| x.max(0.)),
+            "Tanh" => map(&f64::tanh),
+            "Sigmoid" => map(&|x| 1. / (1. + (-x).exp())),
+            "Softplus" => map(&|x| x.exp().ln_1p()),
+            "Neg" => map(&|x| -x),
+            "Identity" => map(&|x| x),
+            "Add" => binary(&|x, y| x + y),
+            "Sub" => binary(&|x, y| x - y),
+            "Mul" => binary(&|x, y| x * y),
+            op => panic!("Unexpected operator {op}"),
+        }
+    }
+
+    /// Decode an exported model and run a batch of inputs through it
+    fn run(bytes: &[u8], batch: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let model = ModelProto::decode(bytes).unwrap();
+        assert_eq!(model.opset_import[0].version, 13);
+        let graph = model.graph.unwrap();
+        let features = batch[0].len();
+        let mut values: HashMap<String, Tensor> = graph
+            .initializer
+            .iter()
+            .map(|t| {
+                assert_eq!(t.data_type, 11);
+                let data = t
+                    .raw_data
+                    .chunks_exact(8)
+                    .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                    .collect();
+                (
+                    t.name.clone(),
+                    (t.dims.iter().map(|d| *d as usize).collect(), data),
+                )
+            })
+            .collect();
+        values.insert(
+            graph.input[0].name.clone(),
+            (vec![batch.len(), features], batch.concat()),
+        );
+        for node in &graph.node {
+            let output = evaluate(node, &values);
+            values.insert(node.output[0].clone(), output);
+        }
+
+        let output = &graph.output[0];
+        let Some(type_proto::Value::TensorType(tensor)) = &output.r#type.as_ref().unwrap().value
+        else {
+            panic!("Output is not a tensor")
+        };
+        let dims = &tensor.shape.as_ref().unwrap().dim;
+        let Some(dimension::Value::DimValue(outputs)) = dims[1].value else {
+            panic!("Output features are not fixed")
+        };
+        let (shape, data) = &values[&output.name];
+        assert_eq!(shape, &vec![batch.len(), outputs as usize]);
+        data.chunks(outputs as usize).map(|c| c.to_vec()).collect()
+    }
+
+    fn batch() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.5, -1.0, 2.0],
+            vec![-3.0, 0.25, 1.5],
+            vec![0.0, 0.0, 0.0],
+        ]
+    }
+
+    #[rstest]
+    #[case::relu(Activation::Relu)]
+    #[case::tanh(Activation::Tanh)]
+    #[case::sigmoid(Activation::Sigmoid)]
+    #[case::leaky_relu(Activation::LeakyRelu(0.01))]
+    #[case::gelu(Activation::Gelu)]
+    #[case::softplus(Activation::Softplus)]
+    fn test_export_matches_forward(#[case] activation: Activation) {
+        let mlp: Mlp<3, 2> = Mlp::from_layer(SizedLayer::<3, 4>::new(activation))
+            .add_layer(SizedLayer::<4, 4>::new(Activation::Tanh))
+            .add_layer(SizedLayer::new(Activation::Identity))
+            .with_seed(3);
+        let outputs = run(&mlp.to_onnx().unwrap(), &batch());
+        for (x, y) in batch().iter().zip(outputs) {
+            let expected = mlp.forward([x[0].into(), x[1].into(), x[2].into()]);
+            for (y, e) in y.iter().zip(expected) {
+                assert_abs_diff_eq!(*y, e.data(), epsilon = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_file() {
+        let mlp = DynMlp::from_layer(DynLayer::new(3, 1, Activation::Sigmoid));
+        let path = std::env::temp_dir().join(format!("ugradrs-{}.onnx", std::process::id()));
+        mlp.export_onnx(&path).unwrap();
+        let bytes = std::fs::read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let outputs = run(&bytes.unwrap(), &batch());
+        let x: Vec<Value> = batch()[1].iter().map(|x| Value::from(*x)).collect();
+        assert_abs_diff_eq!(
+            outputs[1][0],
+            mlp.forward(&x).unwrap()[0].data(),
+            epsilon = 1e-12
+        );
+
+        // Parameters are named as in the state dict
+        let model = ModelProto::decode(mlp.to_onnx().unwrap().as_slice()).unwrap();
+        let names: Vec<String> = model
+            .graph
+            .unwrap()
+            .initializer
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["layer0.weight", "layer0.bias"]);
+    }
+
+    #[test]
+    fn test_export_empty_and_custom() {
+        let outputs = run(&DynMlp::new(3).to_onnx().unwrap(), &batch());
+        assert_eq!(outputs, batch());
+
+        let mlp = DynMlp::from_layer(DynLayer::new(3, 1, Activation::Custom(|x| x.exp())));
+        assert!(matches!(mlp.to_onnx(), Err(OnnxError::Unsupported(_))));
+    }
+
+    /// Field numbers and wire types of the top level fields of a message,
+    /// with the payloads of length delimited fields
+    fn fields(mut bytes: &[u8]) -> Vec<(u64, u64, &[u8])> {
+        let varint = |bytes: &mut &[u8]| {
+            let mut value = 0;
+            for shift in (0..64).step_by(7) {
+                let byte = bytes[0];
+                *bytes = &bytes[1..];
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            value
+        };
+        let mut fields = vec![];
+        while !bytes.is_empty() {
+            let key = varint(&mut bytes);
+            let length = match key & 7 {
+                0 => {
+                    varint(&mut bytes);
+                    0
+                }
+                2 => varint(&mut bytes) as usize,
+                5 => 4,
+                wire_type => panic!("Unexpected wire type {wire_type}"),
+            };
+            let (payload, rest) = bytes.split_at(length);
+            fields.push((key >> 3, key & 7, payload));
+            bytes = rest;
+        }
+        fields
+    }
+
+    fn tags(bytes: &[u8]) -> Vec<(u64, u64)> {
+        fields(bytes).iter().map(|(f, w, _)| (*f, *w)).collect()
+    }
+
+    #[test]
+    fn test_wire_encoding() {
+        // Encodings written out by hand from the field numbers in onnx.proto
+        let attribute = AttributeProto {
+            name: "transA".to_string(),
+            i: Some(0),
+            r#type: 2,
+        };
+        // A zero value is still written, as in proto2
+        assert_eq!(
+            attribute.encode_to_vec(),
+            b"\x0a\x06transA\x18\x00\xa0\x01\x02"
+        );
+
+        let tensor = TensorProto {
+            dims: vec![2, 1],
+            data_type: 11,
+            name: "w".to_string(),
+            raw_data: 1.5f64.to_le_bytes().to_vec(),
+        };
+        assert_eq!(
+            tensor.encode_to_vec(),
+            b"\x08\x02\x08\x01\x10\x0b\x42\x01w\x4a\x08\x00\x00\x00\x00\x00\x00\xf8\x3f"
+        );
+
+        // ValueInfoProto { name, type: TypeProto { tensor_type: { elem_type,
+        // shape: { dim: [{ dim_param }, { dim_value }] } } } }
+        assert_eq!(
+            value_info("x", 3).encode_to_vec(),
+            b"\x0a\x01x\x12\x13\x0a\x11\x08\x0b\x12\x0d\x0a\x07\x12\x05batch\x0a\x02\x08\x03"
+        );
+
+        let mlp = DynMlp::from_layer(DynLayer::new(3, 1, Activation::Sigmoid));
+        let bytes = mlp.to_onnx().unwrap();
+        assert_eq!(bytes[..2], [0x08, 7]);
+        let model = fields(&bytes);
+        assert_eq!(tags(&bytes), [(1, 0), (2, 2), (3, 2), (7, 2), (8, 2)]);
+        assert_eq!(tags(model[4].2), [(2, 0)]);
+
+        // Nodes, name, initializers, input and output
+        let graph = fields(model[3].2);
+        let graph_tags: Vec<u64> = graph.iter().map(|(f, _, _)| *f).collect();
+        assert_eq!(graph_tags, [1, 1, 2, 5, 5, 11, 12]);
+        // Inputs, output, name, op_type and attribute of the Gemm
+        assert_eq!(
+            tags(graph[0].2),
+            [(1, 2), (1, 2), (1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]
+        );
+        let transpose = fields(graph[0].2)[6].2;
+        assert_eq!(transpose, b"\x0a\x06transB\x18\x01\xa0\x01\x02");
+    }
+}
@@ -0,0 +1,466 @@
+pub mod lr_scheduler;
+
+use crate::value::Value;
+
+/// Updates a set of parameters, such as `Mlp::parameters`, from their gradients
+pub trait Optimizer {
+    /// Update every parameter using its current gradient
+    fn step(&mut self);
+
+    /// The parameters being optimized
+    fn parameters(&self) -> &[Value];
+
+    /// The current learning rate
+    fn learning_rate(&self) -> f64;
+
+    /// Change the learning rate used by subsequent steps
+    fn set_learning_rate(&mut self, learning_rate: f64);
+
+    /// Set all parameter gradients back to zero
+    fn zero_grad(&self) {
+        for p in self.parameters() {
+            p.zero_grad()
+        }
+    }
+}
+
+/// Data of a parameter after shrinking it by `learning_rate * weight_decay`
+fn decay(parameter: &Value, learning_rate: f64, weight_decay: f64) -> f64 {
+    parameter.data() * (1. - learning_rate * weight_decay)
+}
+
+/// Stochastic gradient descent with optional momentum and Nesterov momentum
+#[derive(Debug)]
+pub struct Sgd {
+    parameters: Vec<Value>,
+    learning_rate: f64,
+    momentum: f64,
+    nesterov: bool,
+    weight_decay: f64,
+    decoupled_weight_decay: f64,
+    velocity: Vec<f64>,
+}
+
+impl Sgd {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> Self {
+        Self {
+            velocity: vec![0.; parameters.len()],
+            parameters,
+            learning_rate,
+            momentum: 0.,
+            nesterov: false,
+            weight_decay: 0.,
+            decoupled_weight_decay: 0.,
+        }
+    }
+
+    /// Accumulate a velocity, decaying the previous velocity by `momentum` each step
+    pub fn with_momentum(mut self, momentum: f64) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    /// Use Nesterov momentum, evaluating the gradient step after the velocity step
+    pub fn with_nesterov(mut self, nesterov: bool) -> Self {
+        self.nesterov = nesterov;
+        self
+    }
+
+    /// Add `weight_decay * p` to the gradient of each parameter (L2 regularization)
+    pub fn with_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    /// Shrink each parameter by `learning_rate * weight_decay` before the
+    /// update, without adding any nodes to the graph
+    pub fn with_decoupled_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.decoupled_weight_decay = weight_decay;
+        self
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self) {
+        for (p, v) in self.parameters.iter().zip(self.velocity.iter_mut()) {
+            let data = decay(p, self.learning_rate, self.decoupled_weight_decay);
+            let mut g = p.gradient() + self.weight_decay * p.data();
+            if self.momentum != 0. {
+                *v = self.momentum * *v + g;
+                g = if self.nesterov {
+                    g + self.momentum * *v
+                } else {
+                    *v
+                };
+            }
+            p.set_data(data - self.learning_rate * g);
+        }
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate
+    }
+}
+
+/// Adam, scaling each update by running estimates of the first and second
+/// moments of the gradient
+///
+/// Use `Adam::adamw` or `with_decoupled_weight_decay` for AdamW.
+#[derive(Debug)]
+pub struct Adam {
+    parameters: Vec<Value>,
+    learning_rate: f64,
+    betas: (f64, f64),
+    eps: f64,
+    weight_decay: f64,
+    decoupled_weight_decay: f64,
+    steps: i32,
+    first_moment: Vec<f64>,
+    second_moment: Vec<f64>,
+}
+
+impl Adam {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> Self {
+        Self {
+            first_moment: vec![0.; parameters.len()],
+            second_moment: vec![0.; parameters.len()],
+            parameters,
+            learning_rate,
+            betas: (0.9, 0.999),
+            eps: 1e-8,
+            weight_decay: 0.,
+            decoupled_weight_decay: 0.,
+            steps: 0,
+        }
+    }
+
+    /// AdamW, which shrinks each parameter by `learning_rate * weight_decay`
+    /// directly instead of adding the decay to the gradient
+    pub fn adamw(parameters: Vec<Value>, learning_rate: f64, weight_decay: f64) -> Self {
+        Self::new(parameters, learning_rate).with_decoupled_weight_decay(weight_decay)
+    }
+
+    /// Decay rates of the running moment estimates
+    pub fn with_betas(mut self, beta1: f64, beta2: f64) -> Self {
+        self.betas = (beta1, beta2);
+        self
+    }
+
+    /// Term added to the denominator for numerical stability
+    pub fn with_eps(mut self, eps: f64) -> Self {
+        self.eps = eps;
+        self
+    }
+
+    /// Add `weight_decay * p` to the gradient of each parameter (L2 regularization)
+    pub fn with_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    /// Shrink each parameter by `learning_rate * weight_decay` before the
+    /// update, without adding any nodes to the graph
+    pub fn with_decoupled_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.decoupled_weight_decay = weight_decay;
+        self
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self) {
+        self.steps += 1;
+        let (beta1, beta2) = self.betas;
+        let correction1 = 1. - beta1.powi(self.steps);
+        let correction2 = 1. - beta2.powi(self.steps);
+        for ((p, m), v) in self
+            .parameters
+            .iter()
+            .zip(self.first_moment.iter_mut())
+            .zip(self.second_moment.iter_mut())
+        {
+            let data = decay(p, self.learning_rate, self.decoupled_weight_decay);
+            let g = p.gradient() + self.weight_decay * p.data();
+            *m = beta1 * *m + (1. - beta1) * g;
+            *v = beta2 * *v + (1. - beta2) * g * g;
+            let m_hat = *m / correction1;
+            let v_hat = *v / correction2;
+            p.set_data(data - self.learning_rate * m_hat / (v_hat.sqrt() + self.eps));
+        }
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate
+    }
+}
+
+/// RMSProp, scaling each update by a running average of the squared gradient
+#[derive(Debug)]
+pub struct RmsProp {
+    parameters: Vec<Value>,
+    learning_rate: f64,
+    alpha: f64,
+    eps: f64,
+    momentum: f64,
+    decoupled_weight_decay: f64,
+    square_average: Vec<f64>,
+    velocity: Vec<f64>,
+}
+
+impl RmsProp {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> Self {
+        Self {
+            square_average: vec![0.; parameters.len()],
+            velocity: vec![0.; parameters.len()],
+            parameters,
+            learning_rate,
+            alpha: 0.99,
+            eps: 1e-8,
+            momentum: 0.,
+            decoupled_weight_decay: 0.,
+        }
+    }
+
+    /// Decay rate of the running average of squared gradients
+    pub fn with_alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Term added to the denominator for numerical stability
+    pub fn with_eps(mut self, eps: f64) -> Self {
+        self.eps = eps;
+        self
+    }
+
+    /// Accumulate a velocity of the scaled updates
+    pub fn with_momentum(mut self, momentum: f64) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    /// Shrink each parameter by `learning_rate * weight_decay` before the
+    /// update, without adding any nodes to the graph
+    pub fn with_decoupled_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.decoupled_weight_decay = weight_decay;
+        self
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn step(&mut self) {
+        for ((p, s), v) in self
+            .parameters
+            .iter()
+            .zip(self.square_average.iter_mut())
+            .zip(self.velocity.iter_mut())
+        {
+            let data = decay(p, self.learning_rate, self.decoupled_weight_decay);
+            let g = p.gradient();
+            *s = self.alpha * *s + (1. - self.alpha) * g * g;
+            let mut update = g / (s.sqrt() + self.eps);
+            if self.momentum != 0. {
+                *v = self.momentum * *v + update;
+                update = *v;
+            }
+            p.set_data(data - self.learning_rate * update);
+        }
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate
+    }
+}
+
+/// AdaGrad, scaling each update by the accumulated sum of squared gradients
+#[derive(Debug)]
+pub struct AdaGrad {
+    parameters: Vec<Value>,
+    learning_rate: f64,
+    eps: f64,
+    decoupled_weight_decay: f64,
+    square_sum: Vec<f64>,
+}
+
+impl AdaGrad {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> Self {
+        Self {
+            square_sum: vec![0.; parameters.len()],
+            parameters,
+            learning_rate,
+            eps: 1e-10,
+            decoupled_weight_decay: 0.,
+        }
+    }
+
+    /// Term added to the denominator for numerical stability
+    pub fn with_eps(mut self, eps: f64) -> Self {
+        self.eps = eps;
+        self
+    }
+
+    /// Shrink each parameter by `learning_rate * weight_decay` before the
+    /// update, without adding any nodes to the graph
+    pub fn with_decoupled_weight_decay(mut self, weight_decay: f64) -> Self {
+        self.decoupled_weight_decay = weight_decay;
+        self
+    }
+}
+
+impl Optimizer for AdaGrad {
+    fn step(&mut self) {
+        for (p, s) in self.parameters.iter().zip(self.square_sum.iter_mut()) {
+            let data = decay(p, self.learning_rate, self.decoupled_weight_decay);
+            let g = p.gradient();
+            *s += g * g;
+            p.set_data(data - self.learning_rate * g / (s.sqrt() + self.eps));
+        }
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaGrad, Adam, Optimizer, RmsProp, Sgd};
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+    use rstest::rstest;
+
+    fn start() -> Vec<Value> {
+        vec![Value::from(0.0), Value::from(0.0)]
+    }
+
+    #[rstest]
+    #[case::sgd(Box::new(Sgd::new(start(), 0.1)))]
+    #[case::momentum(Box::new(Sgd::new(start(), 0.05).with_momentum(0.9)))]
+    #[case::nesterov(Box::new(Sgd::new(start(), 0.05).with_momentum(0.9).with_nesterov(true)))]
+    #[case::adam(Box::new(Adam::new(start(), 0.1)))]
+    #[case::adamw(Box::new(Adam::adamw(start(), 0.1, 1e-4)))]
+    #[case::rmsprop(Box::new(RmsProp::new(start(), 0.01).with_momentum(0.5)))]
+    #[case::adagrad(Box::new(AdaGrad::new(start(), 0.5)))]
+    fn test_optimizer_converges(#[case] mut optimizer: Box<dyn Optimizer>) {
+        // Minimize (x - 3)^2 + (y + 1)^2
+        let (x, y) = (
+            optimizer.parameters()[0].clone(),
+            optimizer.parameters()[1].clone(),
+        );
+        let mut loss = 0.;
+        for _ in 0..300 {
+            let dx = x.clone() - Value::from(3.0);
+            let dy = y.clone() + 1.0;
+            let l = dx.clone() * dx + dy.clone() * dy;
+            optimizer.zero_grad();
+            l.backward();
+            optimizer.step();
+            loss = l.data();
+        }
+        assert!(loss < 1e-3, "final loss {loss}");
+    }
+
+    fn one_step(optimizer: &mut dyn Optimizer, gradient: f64) -> f64 {
+        let p = &optimizer.parameters()[0];
+        p.zero_grad();
+        p.borrow_mut().gradient = gradient;
+        optimizer.step();
+        optimizer.parameters()[0].data()
+    }
+
+    #[test]
+    fn test_sgd_updates() {
+        let mut sgd = Sgd::new(vec![Value::from(1.0)], 0.1)
+            .with_momentum(0.5)
+            .with_weight_decay(0.1);
+        // g = 2 + 0.1 * 1, v = 2.1
+        assert_abs_diff_eq!(one_step(&mut sgd, 2.0), 1.0 - 0.21, epsilon = 1e-12);
+        // g = 2 + 0.079, v = 0.5 * 2.1 + 2.079
+        assert_abs_diff_eq!(
+            one_step(&mut sgd, 2.0),
+            0.79 - 0.1 * (1.05 + 2.079),
+            epsilon = 1e-12
+        );
+
+        let mut nesterov = Sgd::new(vec![Value::from(1.0)], 0.1)
+            .with_momentum(0.5)
+            .with_nesterov(true);
+        // v = 2, g = 2 + 0.5 * 2
+        assert_abs_diff_eq!(one_step(&mut nesterov, 2.0), 0.7, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_adam_updates() {
+        // The bias corrected first step moves each parameter by the learning rate
+        let mut adam = Adam::new(vec![Value::from(1.0)], 0.1);
+        assert_abs_diff_eq!(one_step(&mut adam, 5.0), 0.9, epsilon = 1e-8);
+        let mut adamw = Adam::adamw(vec![Value::from(1.0)], 0.1, 0.5);
+        assert_abs_diff_eq!(one_step(&mut adamw, 5.0), 0.95 - 0.1, epsilon = 1e-8);
+        let mut adam = Adam::new(vec![Value::from(1.0)], 0.1).with_weight_decay(0.5);
+        assert_abs_diff_eq!(one_step(&mut adam, -0.5), 1.0, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn test_adaptive_updates() {
+        let mut rmsprop = RmsProp::new(vec![Value::from(1.0)], 0.1).with_alpha(0.75);
+        // s = 0.25 * 4, update = 2 / 1
+        assert_abs_diff_eq!(one_step(&mut rmsprop, 2.0), 0.8, epsilon = 1e-8);
+        let mut adagrad = AdaGrad::new(vec![Value::from(1.0)], 0.1);
+        assert_abs_diff_eq!(one_step(&mut adagrad, 3.0), 0.9, epsilon = 1e-8);
+        // s = 9 + 16, update = 4 / 5
+        assert_abs_diff_eq!(one_step(&mut adagrad, 4.0), 0.82, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn test_decoupled_weight_decay() {
+        // With no gradient only the decay is applied, whatever the optimizer
+        let optimizers: Vec<Box<dyn Optimizer>> = vec![
+            Box::new(Sgd::new(vec![Value::from(2.0)], 0.1).with_decoupled_weight_decay(0.5)),
+            Box::new(Adam::adamw(vec![Value::from(2.0)], 0.1, 0.5)),
+            Box::new(RmsProp::new(vec![Value::from(2.0)], 0.1).with_decoupled_weight_decay(0.5)),
+            Box::new(AdaGrad::new(vec![Value::from(2.0)], 0.1).with_decoupled_weight_decay(0.5)),
+        ];
+        for mut optimizer in optimizers {
+            assert_abs_diff_eq!(one_step(optimizer.as_mut(), 0.0), 1.9, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_learning_rate() {
+        let mut sgd = Sgd::new(vec![Value::from(1.0)], 0.1);
+        sgd.set_learning_rate(0.5);
+        assert_eq!(sgd.learning_rate(), 0.5);
+        assert_abs_diff_eq!(one_step(&mut sgd, 1.0), 0.5, epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,396 @@
+use std::f64::consts::PI;
+
+use crate::optim::Optimizer;
+
+/// A learning rate that varies with the number of steps taken
+///
+/// The step may count optimizer steps or epochs, depending on how often the
+/// schedule is applied.
+pub trait LrScheduler {
+    /// Learning rate for a step, counting from zero
+    fn learning_rate(&self, step: usize) -> f64;
+
+    /// Learning rates of the first `steps` steps, e.g. for plotting
+    fn schedule(&self, steps: usize) -> Vec<f64> {
+        (0..steps).map(|s| self.learning_rate(s)).collect()
+    }
+
+    /// Set the learning rate of an optimizer for a step
+    fn apply(&self, optimizer: &mut dyn Optimizer, step: usize) {
+        optimizer.set_learning_rate(self.learning_rate(step))
+    }
+}
+
+/// Cosine interpolation from `start` to `end` as `progress` goes from 0 to 1
+fn cosine(start: f64, end: f64, progress: f64) -> f64 {
+    end + (start - end) * (1. + (PI * progress.clamp(0., 1.)).cos()) / 2.
+}
+
+/// A schedule that also depends on a metric, such as the validation loss,
+/// which is recorded after each step
+pub trait MetricLrScheduler: LrScheduler {
+    /// Record the latest metric, returning the learning rate of the next step
+    fn record(&mut self, metric: f64) -> f64;
+
+    /// Record the latest metric and set the learning rate of an optimizer
+    fn step(&mut self, metric: f64, optimizer: &mut dyn Optimizer) -> f64 {
+        let learning_rate = self.record(metric);
+        optimizer.set_learning_rate(learning_rate);
+        learning_rate
+    }
+}
+
+/// Interpolate the learning rate linearly from `initial` to `end` over
+/// `steps` steps, staying at `end` afterwards
+#[derive(Debug, Clone, Copy)]
+pub struct LinearLr {
+    initial: f64,
+    end: f64,
+    steps: usize,
+}
+
+impl LinearLr {
+    pub fn new(initial: f64, end: f64, steps: usize) -> Self {
+        assert!(steps > 0, "Interpolation must take at least one step");
+        Self {
+            initial,
+            end,
+            steps,
+        }
+    }
+}
+
+impl LrScheduler for LinearLr {
+    fn learning_rate(&self, step: usize) -> f64 {
+        let progress = step.min(self.steps) as f64 / self.steps as f64;
+        self.initial + (self.end - self.initial) * progress
+    }
+}
+
+/// Multiply the learning rate by `gamma` every `step_size` steps
+#[derive(Debug, Clone, Copy)]
+pub struct StepLr {
+    initial: f64,
+    step_size: usize,
+    gamma: f64,
+}
+
+impl StepLr {
+    pub fn new(initial: f64, step_size: usize, gamma: f64) -> Self {
+        assert!(step_size > 0, "Step size must be positive");
+        Self {
+            initial,
+            step_size,
+            gamma,
+        }
+    }
+}
+
+impl LrScheduler for StepLr {
+    fn learning_rate(&self, step: usize) -> f64 {
+        self.initial * self.gamma.powi((step / self.step_size) as i32)
+    }
+}
+
+/// Multiply the learning rate by `gamma` every step
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialLr {
+    initial: f64,
+    gamma: f64,
+}
+
+impl ExponentialLr {
+    pub fn new(initial: f64, gamma: f64) -> Self {
+        Self { initial, gamma }
+    }
+}
+
+impl LrScheduler for ExponentialLr {
+    fn learning_rate(&self, step: usize) -> f64 {
+        self.initial * self.gamma.powi(step as i32)
+    }
+}
+
+/// Anneal the learning rate from `initial` to `minimum` along half a cosine
+/// over `steps` steps, staying at `minimum` afterwards
+#[derive(Debug, Clone, Copy)]
+pub struct CosineAnnealingLr {
+    initial: f64,
+    minimum: f64,
+    steps: usize,
+}
+
+impl CosineAnnealingLr {
+    pub fn new(initial: f64, minimum: f64, steps: usize) -> Self {
+        assert!(steps > 0, "Annealing must take at least one step");
+        Self {
+            initial,
+            minimum,
+            steps,
+        }
+    }
+}
+
+impl LrScheduler for CosineAnnealingLr {
+    fn learning_rate(&self, step: usize) -> f64 {
+        cosine(self.initial, self.minimum, step as f64 / self.steps as f64)
+    }
+}
+
+/// Ramp the learning rate linearly up to that of another schedule over the
+/// first `warmup_steps` steps, then follow the schedule
+///
+/// The wrapped schedule starts counting from zero once the warmup is complete.
+#[derive(Debug, Clone, Copy)]
+pub struct LinearWarmup<S> {
+    warmup_steps: usize,
+    scheduler: S,
+}
+
+impl<S: LrScheduler> LinearWarmup<S> {
+    pub fn new(warmup_steps: usize, scheduler: S) -> Self {
+        Self {
+            warmup_steps,
+            scheduler,
+        }
+    }
+}
+
+impl<S: LrScheduler> LrScheduler for LinearWarmup<S> {
+    fn learning_rate(&self, step: usize) -> f64 {
+        if step < self.warmup_steps {
+            self.scheduler.learning_rate(0) * (step + 1) as f64 / (self.warmup_steps + 1) as f64
+        } else {
+            self.scheduler.learning_rate(step - self.warmup_steps)
+        }
+    }
+}
+
+/// The one-cycle policy, warming up from `maximum / div_factor` to `maximum`
+/// and then annealing to `maximum / (div_factor * final_div_factor)`, both
+/// along a cosine
+#[derive(Debug, Clone, Copy)]
+pub struct OneCycleLr {
+    maximum: f64,
+    total_steps: usize,
+    pct_start: f64,
+    div_factor: f64,
+    final_div_factor: f64,
+}
+
+impl OneCycleLr {
+    pub fn new(maximum: f64, total_steps: usize) -> Self {
+        assert!(total_steps > 1, "One cycle needs at least two steps");
+        Self {
+            maximum,
+            total_steps,
+            pct_start: 0.3,
+            div_factor: 25.,
+            final_div_factor: 1e4,
+        }
+    }
+
+    /// Fraction of the cycle spent increasing the learning rate
+    pub fn with_pct_start(mut self, pct_start: f64) -> Self {
+        self.pct_start = pct_start;
+        self
+    }
+
+    /// Ratio of the maximum to the initial learning rate
+    pub fn with_div_factor(mut self, div_factor: f64) -> Self {
+        self.div_factor = div_factor;
+        self
+    }
+
+    /// Ratio of the initial to the final learning rate
+    pub fn with_final_div_factor(mut self, final_div_factor: f64) -> Self {
+        self.final_div_factor = final_div_factor;
+        self
+    }
+}
+
+impl LrScheduler for OneCycleLr {
+    fn learning_rate(&self, step: usize) -> f64 {
+        let initial = self.maximum / self.div_factor;
+        let last = (self.total_steps - 1) as f64;
+        let peak = (self.pct_start * last).max(1.);
+        let step = step as f64;
+        if step <= peak {
+            cosine(initial, self.maximum, step / peak)
+        } else {
+            cosine(
+                self.maximum,
+                initial / self.final_div_factor,
+                (step - peak) / (last - peak),
+            )
+        }
+    }
+}
+
+/// Reduce the learning rate by `factor` once a metric, such as the
+/// validation loss, has stopped improving for `patience` steps
+///
+/// Unlike the other schedules this depends on the metric, so a step is
+/// taken with `MetricLrScheduler::step`. The learning rate of a step is the
+/// one in use after that many metrics had been recorded.
+#[derive(Debug, Clone)]
+pub struct ReduceLrOnPlateau {
+    factor: f64,
+    patience: usize,
+    threshold: f64,
+    minimum: f64,
+    best: f64,
+    bad_steps: usize,
+    /// The initial learning rate followed by the rate after each metric
+    rates: Vec<f64>,
+}
+
+impl ReduceLrOnPlateau {
+    pub fn new(initial: f64, factor: f64, patience: usize) -> Self {
+        assert!(
+            factor > 0. && factor < 1.,
+            "Factor must be between zero and one"
+        );
+        Self {
+            factor,
+            patience,
+            threshold: 1e-4,
+            minimum: 0.,
+            best: f64::INFINITY,
+            bad_steps: 0,
+            rates: vec![initial],
+        }
+    }
+
+    /// Relative decrease of the metric required to count as an improvement
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Lower bound on the learning rate
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = minimum;
+        self
+    }
+
+    /// Learning rate after each recorded metric
+    pub fn history(&self) -> &[f64] {
+        &self.rates[1..]
+    }
+
+    /// Best metric seen so far
+    pub fn best(&self) -> f64 {
+        self.best
+    }
+}
+
+impl LrScheduler for ReduceLrOnPlateau {
+    fn learning_rate(&self, step: usize) -> f64 {
+        self.rates[step.min(self.rates.len() - 1)]
+    }
+}
+
+impl MetricLrScheduler for ReduceLrOnPlateau {
+    fn record(&mut self, metric: f64) -> f64 {
+        if metric < self.best * (1. - self.threshold) {
+            self.best = metric;
+            self.bad_steps = 0;
+        } else {
+            self.bad_steps += 1;
+        }
+        let mut learning_rate = *self.rates.last().unwrap();
+        if self.bad_steps > self.patience {
+            learning_rate = (learning_rate * self.factor).max(self.minimum);
+            self.bad_steps = 0;
+        }
+        self.rates.push(learning_rate);
+        learning_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CosineAnnealingLr, ExponentialLr, LinearLr, LinearWarmup, LrScheduler, MetricLrScheduler,
+        OneCycleLr, ReduceLrOnPlateau, StepLr,
+    };
+    use crate::optim::{Optimizer, Sgd};
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+
+    fn assert_schedule(scheduler: &dyn LrScheduler, expected: &[f64]) {
+        for (lr, e) in scheduler.schedule(expected.len()).iter().zip(expected) {
+            assert_abs_diff_eq!(lr, e, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_decay_schedules() {
+        assert_schedule(&StepLr::new(1.0, 2, 0.5), &[1.0, 1.0, 0.5, 0.5, 0.25]);
+        assert_schedule(&ExponentialLr::new(2.0, 0.5), &[2.0, 1.0, 0.5, 0.25]);
+        assert_schedule(&CosineAnnealingLr::new(1.0, 0.2, 2), &[1.0, 0.6, 0.2, 0.2]);
+        assert_schedule(&LinearLr::new(1.0, 0.2, 2), &[1.0, 0.6, 0.2, 0.2]);
+    }
+
+    #[test]
+    fn test_linear() {
+        let linear = LinearLr::new(1.0, 0.1, 100);
+        for k in 0..100 {
+            assert_abs_diff_eq!(
+                linear.learning_rate(k),
+                1.0 - 0.9 * (k as f64) / 100.,
+                epsilon = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn test_linear_warmup() {
+        assert_schedule(
+            &LinearWarmup::new(3, ExponentialLr::new(1.0, 0.5)),
+            &[0.25, 0.5, 0.75, 1.0, 0.5],
+        );
+    }
+
+    #[test]
+    fn test_one_cycle() {
+        let schedule = OneCycleLr::new(1.0, 11)
+            .with_pct_start(0.5)
+            .with_div_factor(10.)
+            .with_final_div_factor(100.)
+            .schedule(11);
+        assert_abs_diff_eq!(schedule[0], 0.1, epsilon = 1e-12);
+        assert_abs_diff_eq!(schedule[5], 1.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(schedule[10], 1e-3, epsilon = 1e-12);
+        let peak = schedule.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(peak, 1.0);
+        assert!(schedule[..5].windows(2).all(|w| w[0] < w[1]));
+        assert!(schedule[5..].windows(2).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut sgd = Sgd::new(vec![Value::from(1.0)], 1.0);
+        StepLr::new(0.5, 1, 0.1).apply(&mut sgd, 2);
+        assert_abs_diff_eq!(sgd.learning_rate(), 0.005, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_reduce_on_plateau() {
+        let mut sgd = Sgd::new(vec![Value::from(1.0)], 1.0);
+        let mut plateau = ReduceLrOnPlateau::new(1.0, 0.5, 1).with_minimum(0.3);
+        for metric in [3.0, 2.0, 2.0, 2.5, 1.0, 1.0, 1.0, 1.0, 1.0] {
+            plateau.step(metric, &mut sgd);
+        }
+        assert_eq!(
+            plateau.history(),
+            &[1.0, 1.0, 1.0, 0.5, 0.5, 0.5, 0.3, 0.3, 0.3]
+        );
+        assert_eq!(plateau.best(), 1.0);
+        assert_eq!(sgd.learning_rate(), 0.3);
+        // As a schedule, the rate in use after each number of recorded metrics
+        assert_schedule(&plateau, &[1.0, 1.0, 1.0, 1.0, 0.5]);
+        assert_eq!(plateau.learning_rate(100), 0.3);
+    }
+}
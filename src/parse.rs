@@ -0,0 +1,398 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+
+use crate::value::Value;
+
+/// The reason an expression could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedCharacter(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    InvalidNumber(String),
+    UnknownVariable(String),
+    UnknownFunction(String),
+}
+
+/// Error raised when parsing an expression, with the character position it occurred at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub position: usize,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{c}'"),
+            ParseErrorKind::UnexpectedToken(t) => write!(f, "unexpected '{t}'"),
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ParseErrorKind::InvalidNumber(n) => write!(f, "invalid number '{n}'"),
+            ParseErrorKind::UnknownVariable(v) => write!(f, "unknown variable '{v}'"),
+            ParseErrorKind::UnknownFunction(v) => write!(f, "unknown function '{v}'"),
+        }?;
+        write!(f, " at position {}", self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LeftParen,
+    RightParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Identifier(i) => write!(f, "{i}"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Caret => write!(f, "^"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+        }
+    }
+}
+
+/// Split an expression into tokens, each paired with its starting position
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                Token::Caret
+            }
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            c if c.is_ascii_digit() || c == '.' => {
+                while i + 1 < chars.len() && (chars[i + 1].is_ascii_digit() || chars[i + 1] == '.')
+                {
+                    i += 1;
+                }
+                // Scientific notation, e.g. 1e-4
+                if matches!(chars.get(i + 1), Some('e') | Some('E')) {
+                    let mut j = i + 2;
+                    if matches!(chars.get(j), Some('+') | Some('-')) {
+                        j += 1;
+                    }
+                    if chars.get(j).is_some_and(|c| c.is_ascii_digit()) {
+                        while chars.get(j + 1).is_some_and(|c| c.is_ascii_digit()) {
+                            j += 1;
+                        }
+                        i = j;
+                    }
+                }
+                let text: String = chars[start..=i].iter().collect();
+                Token::Number(text.parse().map_err(|_| ParseError {
+                    kind: ParseErrorKind::InvalidNumber(text.clone()),
+                    position: start,
+                })?)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while i + 1 < chars.len()
+                    && (chars[i + 1].is_alphanumeric()
+                        || chars[i + 1] == '_'
+                        || chars[i + 1] == '.')
+                {
+                    i += 1;
+                }
+                Token::Identifier(chars[start..=i].iter().collect())
+            }
+            c => {
+                return Err(ParseError {
+                    kind: ParseErrorKind::UnexpectedCharacter(c),
+                    position: start,
+                })
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over a list of tokens
+struct Parser<'a, K> {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    end: usize,
+    variables: &'a HashMap<K, Value>,
+}
+
+impl<'a, K: Borrow<str> + Hash + Eq> Parser<'a, K> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(t, _)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.index).map_or(self.end, |(_, p)| *p)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ParseError> {
+        let token = self.tokens.get(self.index).cloned().ok_or(ParseError {
+            kind: ParseErrorKind::UnexpectedEnd,
+            position: self.end,
+        })?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        let (token, position) = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+                position,
+            })
+        }
+    }
+
+    /// expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Value, ParseError> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.index += 1;
+                    value += self.term()?;
+                }
+                Some(Token::Minus) => {
+                    self.index += 1;
+                    value = value - self.term()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    /// term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Value, ParseError> {
+        let mut value = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.index += 1;
+                    value = value * self.unary()?;
+                }
+                Some(Token::Slash) => {
+                    self.index += 1;
+                    value = value / self.unary()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    /// unary := ('-' | '+') unary | power
+    fn unary(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.index += 1;
+                Ok(-self.unary()?)
+            }
+            Some(Token::Plus) => {
+                self.index += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// power := primary ('^' unary)?
+    fn power(&mut self) -> Result<Value, ParseError> {
+        let base = self.primary()?;
+        if let Some(Token::Caret) = self.peek() {
+            self.index += 1;
+            Ok(base.powf(self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// primary := number | variable | function '(' expression ')' | '(' expression ')'
+    fn primary(&mut self) -> Result<Value, ParseError> {
+        let (token, position) = self.next()?;
+        match token {
+            Token::Number(n) => Ok(Value::from(n)),
+            Token::LeftParen => {
+                let value = self.expression()?;
+                self.expect(Token::RightParen)?;
+                Ok(value)
+            }
+            Token::Identifier(name) if self.peek() == Some(&Token::LeftParen) => {
+                let function: fn(Value) -> Value = match name.as_str() {
+                    "tanh" => Value::tanh,
+                    "exp" => Value::exp,
+                    "ln" | "log" => Value::ln,
+                    "abs" => Value::abs,
+                    "relu" => Value::relu,
+                    _ => {
+                        return Err(ParseError {
+                            kind: ParseErrorKind::UnknownFunction(name),
+                            position,
+                        })
+                    }
+                };
+                self.index += 1;
+                let argument = self.expression()?;
+                self.expect(Token::RightParen)?;
+                Ok(function(argument))
+            }
+            Token::Identifier(name) => {
+                self.variables
+                    .get(name.as_str())
+                    .cloned()
+                    .ok_or(ParseError {
+                        kind: ParseErrorKind::UnknownVariable(name),
+                        position,
+                    })
+            }
+            token => Err(ParseError {
+                kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+                position,
+            }),
+        }
+    }
+}
+
+/// Build a Value DAG from a text expression such as `tanh(a*b + c) / (1 + exp(-d))`
+///
+/// Identifiers are looked up in `variables`, so the returned graph shares
+/// those Values and gradients flow back to them on `backward`. Supports
+/// `+ - * /`, `^` (or `**`) for powers, parentheses, numeric constants and the
+/// functions `tanh`, `exp`, `ln` (or `log`), `abs` and `relu`.
+pub fn parse<K: Borrow<str> + Hash + Eq>(
+    expression: &str,
+    variables: &HashMap<K, Value>,
+) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        index: 0,
+        end: expression.chars().count(),
+        variables,
+    };
+    let value = parser.expression()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(token) => Err(ParseError {
+            kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+            position: parser.position(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ParseError, ParseErrorKind};
+    use crate::value::Value;
+    use approx::assert_abs_diff_eq;
+    use std::collections::HashMap;
+
+    fn variables() -> HashMap<&'static str, Value> {
+        HashMap::from([
+            ("a", Value::from(2.0)),
+            ("b", Value::from(-3.0)),
+            ("c", Value::from(10.0)),
+            ("d", Value::from(0.5)),
+        ])
+    }
+
+    #[test]
+    fn test_parse_and_backward() {
+        let vars = variables();
+        let v = parse("tanh(a*b + c) / (1 + exp(-d))", &vars).unwrap();
+        let expected = 4.0_f64.tanh() / (1.0 + (-0.5_f64).exp());
+        assert_abs_diff_eq!(v.data(), expected, epsilon = 1e-12);
+
+        v.backward();
+        let dtanh = (1.0 - 4.0_f64.tanh().powi(2)) / (1.0 + (-0.5_f64).exp());
+        assert_abs_diff_eq!(vars["a"].gradient(), -3.0 * dtanh, epsilon = 1e-9);
+        assert_abs_diff_eq!(vars["c"].gradient(), dtanh, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let vars = variables();
+        assert_eq!(parse("a + b * c", &vars).unwrap().data(), -28.0);
+        assert_eq!(parse("(a + b) * c", &vars).unwrap().data(), -10.0);
+        assert_eq!(parse("-a^2", &vars).unwrap().data(), -4.0);
+        assert_eq!(parse("a ** 3 ** 0", &vars).unwrap().data(), 2.0);
+        assert_eq!(parse("c - a - 1", &vars).unwrap().data(), 7.0);
+        assert_eq!(parse("c / a / 5", &vars).unwrap().data(), 1.0);
+        assert_eq!(parse("1.5e1 * relu(b)", &vars).unwrap().data(), 0.0);
+        assert_eq!(parse("ln(exp(c))", &vars).unwrap().data(), 10.0);
+        assert_eq!(parse("abs(b) - -b", &vars).unwrap().data(), 0.0);
+    }
+
+    #[test]
+    fn test_parse_shares_variables() {
+        let vars = variables();
+        let v = parse("a * a", &vars).unwrap();
+        v.backward();
+        assert_eq!(vars["a"].gradient(), 4.0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let vars = variables();
+        let error = |kind, position| Err(ParseError { kind, position });
+        assert_eq!(
+            parse("a + e", &vars),
+            error(ParseErrorKind::UnknownVariable("e".into()), 4)
+        );
+        assert_eq!(
+            parse("sin(a)", &vars),
+            error(ParseErrorKind::UnknownFunction("sin".into()), 0)
+        );
+        assert_eq!(
+            parse("a + $", &vars),
+            error(ParseErrorKind::UnexpectedCharacter('$'), 4)
+        );
+        assert_eq!(
+            parse("(a + b", &vars),
+            error(ParseErrorKind::UnexpectedEnd, 6)
+        );
+        assert_eq!(
+            parse("a b", &vars),
+            error(ParseErrorKind::UnexpectedToken("b".into()), 2)
+        );
+        assert_eq!(
+            parse("1.2.3", &vars),
+            error(ParseErrorKind::InvalidNumber("1.2.3".into()), 0)
+        );
+        assert_eq!(
+            parse("a + e", &vars).unwrap_err().to_string(),
+            "unknown variable 'e' at position 4"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::nn::state::{size, StateDict, StateError, Tensor};
+
+/// Header entry describing the location of a tensor within the data buffer
+#[derive(Debug, Serialize, Deserialize)]
+struct TensorInfo {
+    dtype: String,
+    shape: Vec<usize>,
+    data_offsets: [usize; 2],
+}
+
+fn invalid(reason: impl Into<String>) -> StateError {
+    StateError::Invalid(reason.into())
+}
+
+/// Convert the bits of an IEEE half precision float
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits >> 15 == 1 { -1. } else { 1. };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let fraction = (bits & 0x3ff) as f64;
+    sign * match exponent {
+        0 => fraction * 2f64.powi(-24),
+        0x1f if fraction == 0. => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1. + fraction / 1024.) * 2f64.powi(exponent - 15),
+    }
+}
+
+/// Decode little-endian values of a safetensors dtype such as `F32`
+fn decode(dtype: &str, bytes: &[u8]) -> Result<Vec<f64>, StateError> {
+    let size = match dtype {
+        "F64" | "I64" | "U64" => 8,
+        "F32" | "I32" | "U32" => 4,
+        "F16" | "BF16" | "I16" | "U16" => 2,
+        "I8" | "U8" | "BOOL" => 1,
+        _ => return Err(invalid(format!("unsupported dtype {dtype}"))),
+    };
+    if bytes.len() % size != 0 {
+        return Err(invalid("tensor data does not match its dtype"));
+    }
+    Ok(bytes
+        .chunks_exact(size)
+        .map(|b| match dtype {
+            "F64" => f64::from_le_bytes(b.try_into().unwrap()),
+            "I64" => i64::from_le_bytes(b.try_into().unwrap()) as f64,
+            "U64" => u64::from_le_bytes(b.try_into().unwrap()) as f64,
+            "F32" => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+            "I32" => i32::from_le_bytes(b.try_into().unwrap()) as f64,
+            "U32" => u32::from_le_bytes(b.try_into().unwrap()) as f64,
+            "F16" => f16_to_f64(u16::from_le_bytes([b[0], b[1]])),
+            "BF16" => f32::from_bits((u16::from_le_bytes([b[0], b[1]]) as u32) << 16) as f64,
+            "I16" => i16::from_le_bytes([b[0], b[1]]) as f64,
+            "U16" => u16::from_le_bytes([b[0], b[1]]) as f64,
+            "I8" => b[0] as i8 as f64,
+            _ => b[0] as f64,
+        })
+        .collect())
+}
+
+/// Encode the entries in the safetensors format, as `F64` tensors
+///
+/// The JSON header lists each entry by name, followed by the raw
+/// little-endian data of every tensor.
+pub fn to_safetensors(state: &StateDict) -> Result<Vec<u8>, StateError> {
+    let mut header = BTreeMap::new();
+    let mut data = Vec::new();
+    for (name, tensor) in state.iter() {
+        let begin = data.len();
+        data.extend(tensor.data.iter().flat_map(|x| x.to_le_bytes()));
+        header.insert(
+            name.as_str(),
+            TensorInfo {
+                dtype: "F64".to_string(),
+                shape: tensor.shape.clone(),
+                data_offsets: [begin, data.len()],
+            },
+        );
+    }
+    let mut header = serde_json::to_string(&header)?;
+    // Pad with spaces so that the data is aligned to 8 bytes
+    header.push_str(&" ".repeat((8 - header.len() % 8) % 8));
+
+    let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+    bytes.extend(header.as_bytes());
+    bytes.extend(data);
+    Ok(bytes)
+}
+
+/// Read every tensor of the safetensors format, converting the values to `f64`
+///
+/// The optional `__metadata__` entry is ignored.
+pub fn from_safetensors(bytes: &[u8]) -> Result<StateDict, StateError> {
+    let header_len = bytes
+        .get(..8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| invalid("unexpected end of data"))?;
+    let header_end = usize::try_from(header_len)
+        .ok()
+        .and_then(|n| n.checked_add(8))
+        .filter(|n| *n <= bytes.len())
+        .ok_or_else(|| invalid("header is truncated"))?;
+    let mut header: BTreeMap<String, serde_json::Value> =
+        serde_json::from_slice(&bytes[8..header_end])?;
+    header.remove("__metadata__");
+
+    let data = &bytes[header_end..];
+    let mut state = StateDict::new();
+    for (name, info) in header {
+        let info: TensorInfo = serde_json::from_value(info)?;
+        let [begin, end] = info.data_offsets;
+        let tensor = data
+            .get(begin..end)
+            .ok_or_else(|| invalid(format!("{name} is out of bounds")))?;
+        let values = decode(&info.dtype, tensor)?;
+        if size(&info.shape) != Some(values.len()) {
+            return Err(invalid(format!("{name} data does not match its shape")));
+        }
+        state.insert(&name, Tensor::new(info.shape, values));
+    }
+    Ok(state)
+}
+
+/// Write the entries to a `.safetensors` file
+pub fn save_safetensors(path: impl AsRef<Path>, state: &StateDict) -> Result<(), StateError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&to_safetensors(state)?)?;
+    Ok(writer.flush()?)
+}
+
+/// Read a `.safetensors` file
+pub fn load_safetensors(path: impl AsRef<Path>) -> Result<StateDict, StateError> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+    from_safetensors(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_safetensors, load_safetensors, save_safetensors, to_safetensors};
+    use crate::nn::activation::Activation;
+    use crate::nn::state::{StateDict, StateError, Tensor};
+    use crate::nn::{Mlp, SizedLayer};
+    use crate::value::Value;
+
+    /// A file as written by the Python library, with a metadata entry
+    fn safetensors(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut state = StateDict::new();
+        state.insert(
+            "weight",
+            Tensor::new(vec![2, 2], vec![0.1, -2.0, 1e-300, 7.0]),
+        );
+        state.insert("bias", Tensor::new(vec![2], vec![0.5, f64::MAX]));
+        let bytes = to_safetensors(&state).unwrap();
+        let header_len = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+        assert_eq!(header_len % 8, 0);
+        assert!(std::str::from_utf8(&bytes[8..8 + header_len])
+            .unwrap()
+            .starts_with(r#"{"bias":{"dtype":"F64","shape":[2],"data_offsets":[0,16]}"#));
+        assert_eq!(from_safetensors(&bytes).unwrap(), state);
+    }
+
+    #[test]
+    fn test_dtypes() {
+        let mut data: Vec<u8> = [1.5_f32, -2.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        // 1.5 and -2 as half precision and bfloat16
+        data.extend([0x00, 0x3e, 0x00, 0xc0]);
+        data.extend([0xc0, 0x3f, 0x00, 0xc0]);
+        data.extend([1, 254]);
+        let header = r#"{"__metadata__":{"format":"pt"},"a":{"dtype":"F32","shape":[2],"data_offsets":[0,8]},"b":{"dtype":"F16","shape":[2],"data_offsets":[8,12]},"c":{"dtype":"BF16","shape":[1,2],"data_offsets":[12,16]},"d":{"dtype":"U8","shape":[2],"data_offsets":[16,18]}}"#;
+        let state = from_safetensors(&safetensors(header, &data)).unwrap();
+        assert_eq!(state.len(), 4);
+        for name in ["a", "b", "c"] {
+            assert_eq!(state.get(name).unwrap().data, vec![1.5, -2.0]);
+        }
+        assert_eq!(state.get("c").unwrap().shape, vec![1, 2]);
+        assert_eq!(state.get("d").unwrap().data, vec![1., 254.]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(from_safetensors(&[1, 2]).is_err());
+        let header = r#"{"a":{"dtype":"F64","shape":[2],"data_offsets":[0,16]}}"#;
+        assert!(matches!(
+            from_safetensors(&safetensors(header, &[0; 8])),
+            Err(StateError::Invalid(_))
+        ));
+        let header = r#"{"a":{"dtype":"F64","shape":[3],"data_offsets":[0,16]}}"#;
+        assert!(from_safetensors(&safetensors(header, &[0; 16])).is_err());
+        let header = r#"{"a":{"dtype":"F64","shape":[4294967296,4294967296,4294967296],"data_offsets":[0,8]}}"#;
+        assert!(matches!(
+            from_safetensors(&safetensors(header, &[0; 8])),
+            Err(StateError::Invalid(_))
+        ));
+        let header = r#"{"a":{"dtype":"F16","shape":[1],"data_offsets":[0,3]}}"#;
+        assert!(matches!(
+            from_safetensors(&safetensors(header, &[0; 3])),
+            Err(StateError::Invalid(_))
+        ));
+        let header = r#"{"a":{"dtype":"C64","shape":[1],"data_offsets":[0,8]}}"#;
+        assert_eq!(
+            from_safetensors(&safetensors(header, &[0; 8]))
+                .unwrap_err()
+                .to_string(),
+            "invalid state dict: unsupported dtype C64"
+        );
+    }
+
+    #[test]
+    fn test_mlp_file() {
+        let mlp: Mlp<2, 1> = Mlp::from_layer(SizedLayer::<2, 3>::new(Activation::Relu))
+            .add_layer(SizedLayer::new(Activation::Identity));
+        let path = std::env::temp_dir().join(format!("ugradrs-{}.safetensors", std::process::id()));
+        save_safetensors(&path, &mlp.state_dict()).unwrap();
+        let state = load_safetensors(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let restored: Mlp<2, 1> = Mlp::from_layer(SizedLayer::<2, 3>::new(Activation::Relu))
+            .add_layer(SizedLayer::new(Activation::Identity));
+        restored.load_state_dict(&state.unwrap()).unwrap();
+        let x = [0.5, -1.0].map(Value::from);
+        assert_eq!(
+            restored.forward(x.clone())[0].data(),
+            mlp.forward(x)[0].data()
+        );
+    }
+}
@@ -0,0 +1,461 @@
+use crate::data::{Batch, DataLoader};
+use crate::nn::loss::Loss;
+use crate::nn::utils::{clip_grad_norm, grad_norm};
+use crate::nn::Mlp;
+use crate::optim::lr_scheduler::LrScheduler;
+use crate::optim::Optimizer;
+use crate::value::Value;
+
+/// A measure of prediction quality reported alongside the loss, averaged over
+/// every sample in an epoch
+pub trait Metric {
+    fn name(&self) -> &str;
+
+    /// Score of a single prediction against its target
+    fn score(&self, prediction: &[f64], target: &[f64]) -> f64;
+}
+
+/// Fraction of predictions with the same sign as their target, for targets of -1 or 1
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SignAccuracy;
+
+impl Metric for SignAccuracy {
+    fn name(&self) -> &str {
+        "accuracy"
+    }
+
+    fn score(&self, prediction: &[f64], target: &[f64]) -> f64 {
+        let correct = prediction
+            .iter()
+            .zip(target)
+            .filter(|(p, t)| (**p > 0.) == (**t > 0.))
+            .count();
+        correct as f64 / target.len() as f64
+    }
+}
+
+/// Fraction of predictions whose largest output matches the largest target,
+/// e.g. for one-hot encoded classes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ArgmaxAccuracy;
+
+fn argmax(x: &[f64]) -> Option<usize> {
+    x.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+}
+
+impl Metric for ArgmaxAccuracy {
+    fn name(&self) -> &str {
+        "accuracy"
+    }
+
+    fn score(&self, prediction: &[f64], target: &[f64]) -> f64 {
+        if argmax(prediction) == argmax(target) {
+            1.
+        } else {
+            0.
+        }
+    }
+}
+
+/// Summary of a single optimizer step
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepReport {
+    pub epoch: usize,
+    /// Number of steps taken before this one, across all epochs
+    pub step: usize,
+    /// Loss of the minibatch, including any regularization penalty
+    pub loss: f64,
+    /// Global gradient norm before clipping
+    pub grad_norm: f64,
+    pub learning_rate: f64,
+}
+
+/// Summary of a complete pass over the dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochReport {
+    pub epoch: usize,
+    /// Mean loss of the minibatches
+    pub loss: f64,
+    /// Name and mean score of each metric
+    pub metrics: Vec<(String, f64)>,
+}
+
+impl EpochReport {
+    /// Value of a metric by name
+    pub fn metric(&self, name: &str) -> Option<f64> {
+        self.metrics
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, m)| *m)
+    }
+}
+
+/// Hooks called by a `Trainer` as training progresses
+pub trait Callback {
+    fn on_step(&mut self, _report: &StepReport) {}
+    fn on_epoch(&mut self, _report: &EpochReport) {}
+}
+
+struct OnStep<F>(F);
+
+impl<F: FnMut(&StepReport)> Callback for OnStep<F> {
+    fn on_step(&mut self, report: &StepReport) {
+        (self.0)(report)
+    }
+}
+
+struct OnEpoch<F>(F);
+
+impl<F: FnMut(&EpochReport)> Callback for OnEpoch<F> {
+    fn on_epoch(&mut self, report: &EpochReport) {
+        (self.0)(report)
+    }
+}
+
+/// Runs the forward, loss, backward and update loop of an `Mlp` over a dataset
+///
+/// Each step evaluates a minibatch, averaging the loss of each sample and
+/// adding the regularization penalty of the model, then updates the
+/// parameters with the optimizer and applies the model constraints.
+pub struct Trainer<'a, const I: usize, const O: usize> {
+    model: &'a Mlp<I, O>,
+    loss: Box<dyn Loss + 'a>,
+    optimizer: Box<dyn Optimizer + 'a>,
+    scheduler: Option<Box<dyn LrScheduler + 'a>>,
+    batch_size: Option<usize>,
+    max_grad_norm: Option<f64>,
+    metrics: Vec<Box<dyn Metric + 'a>>,
+    callbacks: Vec<Box<dyn Callback + 'a>>,
+    steps: usize,
+    history: Vec<EpochReport>,
+}
+
+impl<'a, const I: usize, const O: usize> Trainer<'a, I, O> {
+    /// Create a trainer for a model, typically with an optimizer over `model.parameters()`
+    pub fn new(model: &'a Mlp<I, O>, loss: impl Loss + 'a, optimizer: impl Optimizer + 'a) -> Self {
+        Self {
+            model,
+            loss: Box::new(loss),
+            optimizer: Box::new(optimizer),
+            scheduler: None,
+            batch_size: None,
+            max_grad_norm: None,
+            metrics: Vec::new(),
+            callbacks: Vec::new(),
+            steps: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// Number of samples in each minibatch of `fit`, defaulting to the whole dataset
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "Batch size must be positive");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Set the learning rate of the optimizer before every step
+    pub fn with_scheduler(mut self, scheduler: impl LrScheduler + 'a) -> Self {
+        self.scheduler = Some(Box::new(scheduler));
+        self
+    }
+
+    /// Clip the global gradient norm before every step
+    pub fn with_clip_grad_norm(mut self, max_norm: f64) -> Self {
+        self.max_grad_norm = Some(max_norm);
+        self
+    }
+
+    /// Report a metric at the end of each epoch
+    pub fn with_metric(mut self, metric: impl Metric + 'a) -> Self {
+        self.metrics.push(Box::new(metric));
+        self
+    }
+
+    pub fn with_callback(mut self, callback: impl Callback + 'a) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Call a closure after every step
+    pub fn on_step(self, f: impl FnMut(&StepReport) + 'a) -> Self {
+        self.with_callback(OnStep(f))
+    }
+
+    /// Call a closure after every epoch
+    pub fn on_epoch(self, f: impl FnMut(&EpochReport) + 'a) -> Self {
+        self.with_callback(OnEpoch(f))
+    }
+
+    /// The optimizer updating the model
+    pub fn optimizer(&self) -> &dyn Optimizer {
+        self.optimizer.as_ref()
+    }
+
+    /// Report of every epoch trained so far
+    pub fn history(&self) -> &[EpochReport] {
+        &self.history
+    }
+
+    /// Train for a number of epochs, returning the report of the last
+    ///
+    /// Returns None without training if `data` is empty
+    pub fn fit(&mut self, data: &[([f64; I], [f64; O])], epochs: usize) -> Option<&EpochReport> {
+        let batch_size = self.batch_size.unwrap_or(data.len()).max(1);
+        for _ in 0..epochs {
+            self.epoch(data.chunks(batch_size).map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|(x, y)| (x.map(Value::from), y.map(Value::from)))
+                    .collect()
+            }))?;
+        }
+        self.history.last()
+    }
+
+    /// Train for a number of epochs on the minibatches of a `DataLoader`, e.g.
+    /// to shuffle or weight the samples, returning the report of the last
+    ///
+    /// Returns None without training if the loader has no batches
+    pub fn fit_loader(
+        &mut self,
+        loader: &mut DataLoader<I, O>,
+        epochs: usize,
+    ) -> Option<&EpochReport> {
+        for _ in 0..epochs {
+            self.epoch(loader.batches())?;
+        }
+        self.history.last()
+    }
+
+    /// Run a single pass over the dataset, or None if there were no samples,
+    /// in which case nothing is recorded
+    fn epoch(&mut self, batches: impl Iterator<Item = Batch<I, O>>) -> Option<&EpochReport> {
+        let epoch = self.history.len();
+        let mut losses = Vec::new();
+        let mut samples = 0;
+        let mut scores = vec![0.; self.metrics.len()];
+        for batch in batches {
+            samples += batch.len();
+            let (loss, batch_scores) = self.step(epoch, &batch);
+            losses.push(loss);
+            for (s, b) in scores.iter_mut().zip(batch_scores) {
+                *s += b
+            }
+        }
+        if samples == 0 {
+            return None;
+        }
+        let report = EpochReport {
+            epoch,
+            loss: losses.iter().sum::<f64>() / losses.len() as f64,
+            metrics: self
+                .metrics
+                .iter()
+                .zip(scores)
+                .map(|(m, s)| (m.name().to_string(), s / samples as f64))
+                .collect(),
+        };
+        for c in self.callbacks.iter_mut() {
+            c.on_epoch(&report)
+        }
+        self.history.push(report);
+        self.history.last()
+    }
+
+    /// Update the model from a minibatch, returning the loss and the summed
+    /// score of each metric
+    fn step(&mut self, epoch: usize, batch: &Batch<I, O>) -> (f64, Vec<f64>) {
+        let mut scores = vec![0.; self.metrics.len()];
+        let losses: Vec<Value> = batch
+            .iter()
+            .map(|(x, y)| {
+                let prediction = self.model.forward(x.clone());
+                let data = prediction.each_ref().map(|p| p.data());
+                let target = y.each_ref().map(|t| t.data());
+                for (s, m) in scores.iter_mut().zip(&self.metrics) {
+                    *s += m.score(&data, &target)
+                }
+                self.loss.loss(&prediction, y)
+            })
+            .collect();
+        let loss = losses.into_iter().sum::<Value>() / batch.len() as f64 + self.model.penalty();
+
+        self.optimizer.zero_grad();
+        loss.backward();
+        let grad_norm = match self.max_grad_norm {
+            Some(max_norm) => clip_grad_norm(self.optimizer.parameters(), max_norm),
+            None => grad_norm(self.optimizer.parameters()),
+        };
+        if let Some(scheduler) = &self.scheduler {
+            scheduler.apply(self.optimizer.as_mut(), self.steps);
+        }
+        self.optimizer.step();
+        self.model.apply_constraints();
+
+        let report = StepReport {
+            epoch,
+            step: self.steps,
+            loss: loss.data(),
+            grad_norm,
+            learning_rate: self.optimizer.learning_rate(),
+        };
+        for c in self.callbacks.iter_mut() {
+            c.on_step(&report)
+        }
+        self.steps += 1;
+        (loss.data(), scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArgmaxAccuracy, Callback, EpochReport, Metric, SignAccuracy, StepReport, Trainer};
+    use crate::data::DataLoader;
+    use crate::nn::activation::Activation;
+    use crate::nn::loss::{HingeLoss, MseLoss};
+    use crate::nn::{Mlp, SizedLayer};
+    use crate::optim::lr_scheduler::StepLr;
+    use crate::optim::Sgd;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+
+    fn data() -> Vec<([f64; 3], [f64; 1])> {
+        vec![
+            ([2.0, 3.0, -1.0], [1.0]),
+            ([3.0, -1.0, 0.5], [-1.0]),
+            ([0.5, 1.0, 1.0], [-1.0]),
+            ([1.0, 1.0, -1.0], [1.0]),
+        ]
+    }
+
+    fn mlp() -> Mlp<3, 1> {
+        let mut rng = StdRng::seed_from_u64(3);
+        Mlp::<3, 4>::new_with_rng(Activation::Relu, &mut rng)
+            .add_layer(SizedLayer::<4, 4>::new_with_rng(Activation::Relu, &mut rng))
+            .add_layer(SizedLayer::new_with_rng(Activation::Identity, &mut rng))
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        steps: Vec<StepReport>,
+        epochs: usize,
+    }
+
+    impl Callback for &RefCell<Counter> {
+        fn on_step(&mut self, report: &StepReport) {
+            self.borrow_mut().steps.push(report.clone())
+        }
+
+        fn on_epoch(&mut self, _report: &EpochReport) {
+            self.borrow_mut().epochs += 1
+        }
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let mlp = mlp();
+        let counter = RefCell::new(Counter::default());
+        let mut epochs = Vec::new();
+        let mut trainer = Trainer::new(&mlp, MseLoss::default(), Sgd::new(mlp.parameters(), 0.05))
+            .with_scheduler(StepLr::new(0.1, 2, 0.5))
+            .with_clip_grad_norm(1.0)
+            .with_callback(&counter)
+            .on_epoch(|r| epochs.push(r.epoch));
+        trainer.fit_loader(
+            &mut DataLoader::new(&data(), 3).with_shuffle(true).with_seed(5),
+            3,
+        );
+        drop(trainer);
+
+        let counter = counter.into_inner();
+        assert_eq!(epochs, vec![0, 1, 2]);
+        assert_eq!(counter.epochs, 3);
+        // Two minibatches per epoch, the second holding a single sample
+        let steps: Vec<(usize, usize)> = counter.steps.iter().map(|s| (s.epoch, s.step)).collect();
+        assert_eq!(steps, vec![(0, 0), (0, 1), (1, 2), (1, 3), (2, 4), (2, 5)]);
+        let rates: Vec<f64> = counter.steps.iter().map(|s| s.learning_rate).collect();
+        assert_eq!(rates, vec![0.1, 0.1, 0.05, 0.05, 0.025, 0.025]);
+    }
+
+    /// Train the seeded model on shuffled minibatches, returning every report
+    fn fit() -> Vec<EpochReport> {
+        let mlp = mlp();
+        let mut trainer = Trainer::new(
+            &mlp,
+            HingeLoss::default(),
+            Sgd::new(mlp.parameters(), 0.05).with_momentum(0.9),
+        )
+        .with_metric(SignAccuracy);
+        let data = data();
+        let mut loader = DataLoader::new(&data, 2).with_shuffle(true).with_seed(5);
+        trainer.fit_loader(&mut loader, 1);
+        trainer.fit_loader(&mut loader, 99);
+        trainer.history().to_vec()
+    }
+
+    #[test]
+    fn test_fit() {
+        let history = fit();
+        assert_eq!(history.len(), 100);
+        let last = history.last().unwrap();
+        assert!(last.loss < history[0].loss);
+        assert!(last.metric("accuracy").is_some());
+        assert_eq!(last.metric("precision"), None);
+        // The seeded model and loader always train the same way
+        assert_eq!(fit(), history);
+    }
+
+    #[test]
+    fn test_fit_batch_size() {
+        let mlp = mlp();
+        let mut steps = 0;
+        let mut trainer = Trainer::new(&mlp, MseLoss::default(), Sgd::new(mlp.parameters(), 0.05))
+            .with_batch_size(3)
+            .on_step(|_| steps += 1);
+        let report = trainer.fit(&data(), 2).unwrap().clone();
+        assert_eq!(report.epoch, 1);
+        drop(trainer);
+        // Two minibatches per epoch
+        assert_eq!(steps, 4);
+
+        let mut trainer = Trainer::new(&mlp, MseLoss::default(), Sgd::new(mlp.parameters(), 0.05));
+        trainer.fit(&data(), 1);
+        assert_eq!(trainer.history().len(), 1);
+    }
+
+    #[test]
+    fn test_fit_empty() {
+        let mlp = mlp();
+        let counter = RefCell::new(Counter::default());
+        let mut trainer = Trainer::new(&mlp, MseLoss::default(), Sgd::new(mlp.parameters(), 0.05))
+            .with_callback(&counter);
+        let empty: Vec<([f64; 3], [f64; 1])> = vec![];
+        assert!(trainer.fit(&empty, 2).is_none());
+        assert!(trainer
+            .fit_loader(&mut DataLoader::new(&empty, 2), 2)
+            .is_none());
+        assert!(trainer.history().is_empty());
+        drop(trainer);
+
+        let counter = counter.into_inner();
+        assert_eq!((counter.steps.len(), counter.epochs), (0, 0));
+    }
+
+    #[test]
+    fn test_metrics() {
+        assert_eq!(SignAccuracy.score(&[0.5, -2.0], &[1.0, 1.0]), 0.5);
+        assert_eq!(
+            ArgmaxAccuracy.score(&[0.1, 2.0, 0.3], &[0.0, 1.0, 0.0]),
+            1.0
+        );
+        assert_eq!(
+            ArgmaxAccuracy.score(&[3.1, 2.0, 0.3], &[0.0, 1.0, 0.0]),
+            0.0
+        );
+    }
+}
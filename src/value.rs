@@ -1,3 +1,4 @@
+use crate::anomaly;
 use std::ops::AddAssign;
 use std::{
     cell::RefCell,
@@ -5,17 +6,27 @@ use std::{
     fmt::Debug,
     fmt::Display,
     hash::Hash,
-    iter::Sum,
-    ops::{Add, Deref, Div, Mul, Sub},
+    iter::{Product, Sum},
+    ops::{Add, Deref, Div, Mul, Neg, Sub},
     rc::Rc,
 };
 
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
     Add,
+    Subtract,
+    Negate,
     Multiply,
+    Divide,
+    /// Sum of any number of children
+    Sum,
+    /// Dot product of the first half of the children with the second half
+    Dot,
     Tanh,
     Exponent,
+    Log,
+    Abs,
     Pow,
     Relu,
 }
@@ -24,9 +35,16 @@ impl From<Operation> for String {
     fn from(value: Operation) -> Self {
         match value {
             Operation::Add => "+",
+            Operation::Subtract => "-",
+            Operation::Negate => "neg",
             Operation::Multiply => "*",
+            Operation::Divide => "/",
+            Operation::Sum => "sum",
+            Operation::Dot => "dot",
             Operation::Tanh => "tanh",
             Operation::Exponent => "e^",
+            Operation::Log => "ln",
+            Operation::Abs => "abs",
             Operation::Relu => "ReLU",
             Operation::Pow => "**",
         }
@@ -34,11 +52,40 @@ impl From<Operation> for String {
     }
 }
 
+impl Operation {
+    /// Calculate the result of the operation from the data of its children
+    pub fn evaluate(self, inputs: &[f64]) -> f64 {
+        match self {
+            Operation::Add => inputs.iter().sum(),
+            Operation::Subtract => inputs[0] - inputs[1],
+            Operation::Negate => -inputs[0],
+            Operation::Multiply => inputs.iter().product(),
+            Operation::Divide => inputs[0] / inputs[1],
+            Operation::Sum => inputs.iter().sum(),
+            Operation::Dot => {
+                let (a, b) = inputs.split_at(inputs.len() / 2);
+                a.iter().zip(b).map(|(a, b)| a * b).sum()
+            }
+            Operation::Tanh => inputs[0].tanh(),
+            Operation::Exponent => inputs[0].exp(),
+            Operation::Log => inputs[0].ln(),
+            Operation::Abs => inputs[0].abs(),
+            Operation::Pow => inputs[0].powf(inputs[1]),
+            Operation::Relu => inputs[0].max(0.0),
+        }
+    }
+}
+
 /// Implementation of an equation value
 ///
 /// Actual is kept internally so that Value can be freely cloned
 /// without actually copying data
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Values are compared by identity: two Values are equal only when they are
+/// clones of the same node, so `Value::from(1.0) != Value::from(1.0)`. This
+/// keeps distinct nodes with equal data apart when used as keys. Compare
+/// `data()` to check for equal numbers.
+#[derive(Debug, Clone)]
 pub struct Value(Rc<RefCell<ValueInternal>>);
 
 /// Internal holder of Value information
@@ -49,6 +96,10 @@ pub struct ValueInternal {
     pub gradient: f64,
     pub label: Option<String>,
     pub uuid: String,
+    /// Incremented whenever the data changes
+    version: u64,
+    /// Versions of the children when the data was last computed
+    seen: Vec<u64>,
 }
 
 impl Deref for Value {
@@ -59,6 +110,14 @@ impl Deref for Value {
     }
 }
 
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Value {}
+
 impl Hash for Value {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.0.borrow().hash(state)
@@ -81,14 +140,67 @@ impl Value {
         Value(Rc::new(RefCell::new(value)))
     }
 
+    /// Create a new node as the result of applying an operation to children
+    fn from_operation(operation: Operation, children: Vec<Value>) -> Self {
+        let inputs: Vec<f64> = children.iter().map(|c| c.data()).collect();
+        let v = Value::from_parts(operation.evaluate(&inputs), Some(operation), children, None);
+        anomaly::check_forward(&v);
+        v
+    }
+
+    /// Create a node with the given data, operation and children as they are,
+    /// without evaluating the operation
+    pub(crate) fn from_parts(
+        data: f64,
+        operation: Option<Operation>,
+        children: Vec<Value>,
+        label: Option<String>,
+    ) -> Self {
+        Value::new(ValueInternal::new(data, children, operation, label))
+    }
+
+    /// Create a labelled leaf Value
+    pub fn named(label: &str, data: f64) -> Self {
+        Value::from(data).with_label(label)
+    }
+
+    /// Attach a label to the Value, returning it for chaining
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
+    /// Set the label of the Value
+    pub fn set_label(&self, label: &str) {
+        self.borrow_mut().label = Some(label.to_string());
+    }
+
+    /// The label of the Value (if any)
+    pub fn label(&self) -> Option<String> {
+        self.borrow().label.clone()
+    }
+
+    /// Find a node by label within the graph leading to this Value
+    pub fn find(&self, label: &str) -> Option<Value> {
+        self.topological_order()
+            .into_iter()
+            .rev()
+            .find(|v| v.borrow().label.as_deref() == Some(label))
+    }
+
     /// Access the internal f64 of the Value
     pub fn data(&self) -> f64 {
         self.borrow().data
     }
 
     /// Set the internal f64 of the Value
+    ///
+    /// Nodes that depend on this Value are not updated until `recompute` or
+    /// `recompute_dirty` is called
     pub fn set_data(&self, value: f64) {
-        self.borrow_mut().data = value;
+        let mut internal = self.borrow_mut();
+        internal.data = value;
+        internal.version += 1;
     }
 
     /// The gradient of the current value
@@ -113,56 +225,53 @@ impl Value {
         self.borrow().operation
     }
 
+    /// Create a single node holding the dot product of two equal length sets of Values
+    pub fn dot(a: &[Value], b: &[Value]) -> Value {
+        assert_eq!(
+            a.len(),
+            b.len(),
+            "Dot product of Values with different lengths"
+        );
+        Value::from_operation(Operation::Dot, a.iter().chain(b).cloned().collect())
+    }
+
     /// Apply the tanh operation to the node, creating a new Value
     pub fn tanh(self) -> Value {
-        let d = self.borrow().data.tanh();
-        Value::new(ValueInternal::new(
-            d,
-            vec![self],
-            Some(Operation::Tanh),
-            None,
-        ))
+        Value::from_operation(Operation::Tanh, vec![self])
     }
 
     /// Apply the exp operation to the node, creating a new Value
     pub fn exp(self) -> Self {
-        let d = self.borrow().data.exp();
-        Value::new(ValueInternal::new(
-            d,
-            vec![self],
-            Some(Operation::Exponent),
-            None,
-        ))
+        Value::from_operation(Operation::Exponent, vec![self])
+    }
+
+    /// Apply the natural logarithm to the node, creating a new Value
+    pub fn ln(self) -> Self {
+        Value::from_operation(Operation::Log, vec![self])
+    }
+
+    /// Take the absolute value of the node, creating a new Value
+    pub fn abs(self) -> Self {
+        Value::from_operation(Operation::Abs, vec![self])
     }
 
     /// Apply the powf operation to the node, creating a new Value
     pub fn powf(self, value: Value) -> Self {
-        let d = self.data().powf(value.data());
-        Value::new(ValueInternal::new(
-            d,
-            vec![self, value],
-            Some(Operation::Pow),
-            None,
-        ))
+        Value::from_operation(Operation::Pow, vec![self, value])
     }
 
     /// Apply the relu operation to the node
     pub fn relu(self) -> Self {
-        let d = self.data().max(0.0);
-        Value::new(ValueInternal::new(
-            d,
-            vec![self],
-            Some(Operation::Relu),
-            None,
-        ))
+        Value::from_operation(Operation::Relu, vec![self])
     }
-    /// Apply backward propagation of the gradient for this Value and all children in our graph
-    pub fn backward(&self) {
+
+    /// All nodes in the graph leading to this Value, ordered so that each node
+    /// comes after all of its children
+    pub(crate) fn topological_order(&self) -> Vec<Value> {
         let mut topo = Vec::new();
         let mut visited = HashSet::new();
         fn build_topo(node: Value, visited: &mut HashSet<Value>, topo: &mut Vec<Value>) {
-            if !visited.contains(&node) {
-                visited.insert(node.clone());
+            if visited.insert(node.clone()) {
                 for child in node.children() {
                     build_topo(child, visited, topo)
                 }
@@ -170,19 +279,84 @@ impl Value {
             }
         }
         build_topo(self.clone(), &mut visited, &mut topo);
+        topo
+    }
+
+    /// Re-run the forward pass over the existing graph, returning the new data
+    ///
+    /// Every node that is the result of an operation is re-evaluated from its
+    /// children, so changes made to leaves with `set_data` are reflected
+    pub fn recompute(&self) -> f64 {
+        for node in self.topological_order() {
+            node.forward_internal();
+        }
+        self.data()
+    }
+
+    /// Re-run the forward pass only for nodes downstream of changed Values
+    ///
+    /// Every Value carries a version that is incremented by `set_data`, and
+    /// each node remembers the versions of its children from when it was last
+    /// evaluated. Only nodes with a changed child are re-evaluated, so leaves
+    /// shared between several graphs can be recomputed from any of them.
+    pub fn recompute_dirty(&self) -> f64 {
+        for node in self.topological_order() {
+            let changed = {
+                let internal = node.borrow();
+                internal
+                    .children
+                    .iter()
+                    .zip(&internal.seen)
+                    .any(|(c, seen)| c.borrow().version != *seen)
+            };
+            if changed {
+                node.forward_internal();
+            }
+        }
+        self.data()
+    }
+
+    fn forward_internal(&self) {
+        if let Some(operation) = self.operation() {
+            let children = self.children();
+            let inputs: Vec<f64> = children.iter().map(|c| c.data()).collect();
+            {
+                let mut internal = self.borrow_mut();
+                internal.data = operation.evaluate(&inputs);
+                internal.version += 1;
+                internal.seen = children.iter().map(|c| c.borrow().version).collect();
+            }
+            anomaly::check_forward(self);
+        }
+    }
+
+    /// Apply backward propagation of the gradient for this Value and all children in our graph
+    ///
+    /// See `anomaly::detect_anomaly` to find where NaN or infinite gradients originate
+    pub fn backward(&self) {
+        let topo = self.topological_order();
         self.borrow_mut().gradient = 1.0;
         for node in topo.into_iter().rev() {
-            node.backward_internal()
+            node.backward_internal();
+            anomaly::check_backward(&node);
         }
     }
 
     fn backward_internal(&self) {
         match self.operation() {
-            Some(Operation::Add) => {
+            Some(Operation::Add) | Some(Operation::Sum) => {
                 for child in self.children().iter_mut() {
                     child.borrow_mut().gradient += self.gradient();
                 }
             }
+            Some(Operation::Dot) => {
+                let children = self.children();
+                let data: Vec<f64> = children.iter().map(|c| c.data()).collect();
+                let n = children.len() / 2;
+                for (i, child) in children.iter().enumerate() {
+                    child.borrow_mut().gradient += data[(i + n) % (2 * n)] * self.gradient();
+                }
+            }
             Some(Operation::Multiply) => {
                 let first = self.children()[0].data();
                 let second = self.children()[1].data();
@@ -200,6 +374,40 @@ impl Value {
                     child.borrow_mut().gradient += self.data() * self.gradient()
                 }
             }
+            Some(Operation::Subtract) => {
+                let children = self.children();
+                children[0].borrow_mut().gradient += self.gradient();
+                children[1].borrow_mut().gradient -= self.gradient();
+            }
+            Some(Operation::Negate) => {
+                self.children()[0].borrow_mut().gradient -= self.gradient();
+            }
+            Some(Operation::Divide) => {
+                let children = self.children();
+                let numerator = children[0].data();
+                let denominator = children[1].data();
+                children[0].borrow_mut().gradient += self.gradient() / denominator;
+                children[1].borrow_mut().gradient +=
+                    -numerator / (denominator * denominator) * self.gradient();
+            }
+            Some(Operation::Log) => {
+                let children = self.children();
+                let child = &children[0];
+                let x = child.data();
+                child.borrow_mut().gradient += self.gradient() / x;
+            }
+            Some(Operation::Abs) => {
+                let children = self.children();
+                let child = &children[0];
+                let x = child.data();
+                child.borrow_mut().gradient += if x > 0. {
+                    self.gradient()
+                } else if x < 0. {
+                    -self.gradient()
+                } else {
+                    0.
+                };
+            }
             Some(Operation::Pow) => {
                 let base = &self.children()[0];
                 let power = &self.children()[1];
@@ -238,6 +446,7 @@ impl ValueInternal {
         operation: Option<Operation>,
         label: Option<String>,
     ) -> Self {
+        let seen = children.iter().map(|c| c.borrow().version).collect();
         Self {
             data,
             children,
@@ -245,6 +454,8 @@ impl ValueInternal {
             gradient: 0.,
             label,
             uuid: uuid::Uuid::new_v4().to_string(),
+            version: 0,
+            seen,
         }
     }
 }
@@ -258,12 +469,7 @@ impl Add for Value {
     type Output = Value;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Value::new(ValueInternal::new(
-            self.data() + rhs.data(),
-            vec![rhs, self],
-            Some(Operation::Add),
-            None,
-        ))
+        Value::from_operation(Operation::Add, vec![self, rhs])
     }
 }
 
@@ -283,7 +489,15 @@ impl Sub for Value {
     type Output = Value;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        self + rhs * Value::from(-1.0)
+        Value::from_operation(Operation::Subtract, vec![self, rhs])
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Self::Output {
+        Value::from_operation(Operation::Negate, vec![self])
     }
 }
 
@@ -291,12 +505,7 @@ impl Mul for Value {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        Value::new(ValueInternal::new(
-            self.data() * rhs.data(),
-            vec![self, rhs],
-            Some(Operation::Multiply),
-            None,
-        ))
+        Value::from_operation(Operation::Multiply, vec![self, rhs])
     }
 }
 
@@ -312,7 +521,7 @@ impl Div for Value {
     type Output = Self;
 
     fn div(self, rhs: Self) -> Self::Output {
-        self * rhs.powf(Value::from(-1.0))
+        Value::from_operation(Operation::Divide, vec![self, rhs])
     }
 }
 
@@ -334,12 +543,22 @@ impl PartialEq for ValueInternal {
     }
 }
 impl Sum for Value {
-    fn sum<I: Iterator<Item = Self>>(mut iter: I) -> Self {
-        let mut sum = iter.next().unwrap();
-        for value in iter {
-            sum += value
+    /// Create a single node summing all Values, or zero if there are none
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        let children: Vec<Value> = iter.collect();
+        if children.is_empty() {
+            Value::from(0.0)
+        } else {
+            Value::from_operation(Operation::Sum, children)
         }
-        sum
+    }
+}
+
+impl Product for Value {
+    /// Multiply all Values together, or one if there are none
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.reduce(|a, b| a * b)
+            .unwrap_or_else(|| Value::from(1.0))
     }
 }
 
@@ -421,4 +640,116 @@ mod tests {
         assert_abs_diff_eq!(x2w2.gradient(), 0.5, epsilon = 0.001);
         assert_abs_diff_eq!(x1w1.gradient(), 0.5, epsilon = 0.001);
     }
+
+    #[test]
+    fn test_recompute() {
+        let a = Value::from(2.0);
+        let b = Value::from(-3.0);
+        let c = Value::from(10.0);
+        let d = (a.clone() * b.clone() + c.clone()).tanh();
+        let e = d.clone() * a.clone();
+
+        a.set_data(-1.0);
+        assert_eq!(d.data(), 4.0_f64.tanh()); // Stale until recomputed
+        assert_eq!(e.recompute(), -(13.0_f64.tanh()));
+        assert_eq!(d.data(), 13.0_f64.tanh());
+    }
+
+    #[test]
+    fn test_recompute_dirty() {
+        let a = Value::from(2.0);
+        let b = Value::from(-3.0);
+        let ab = a.clone() * b.clone();
+        let c = Value::from(10.0);
+        let cc = c.clone() * c.clone();
+        let d = ab.clone() + cc.clone();
+
+        b.set_data(4.0);
+        // Corrupt an untouched branch to show that it is skipped
+        cc.borrow_mut().data = 0.0;
+        assert_eq!(d.recompute_dirty(), 8.0);
+        assert_eq!(ab.data(), 8.0);
+        assert_eq!(d.recompute_dirty(), 8.0);
+
+        // A full recompute fixes everything
+        assert_eq!(d.recompute(), 108.0);
+    }
+
+    #[test]
+    fn test_recompute_dirty_shared_leaf() {
+        let a = Value::from(2.0);
+        let b = a.clone() * Value::from(3.0);
+        let c = a.clone() + Value::from(1.0);
+
+        a.set_data(5.0);
+        assert_eq!(b.recompute_dirty(), 15.0);
+        // The second graph still sees the change to the shared leaf
+        assert_eq!(c.recompute_dirty(), 6.0);
+    }
+
+    #[test]
+    fn test_identity() {
+        let a = Value::from(1.0);
+        let b = Value::from(1.0);
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+        // Distinct nodes with equal data are all part of the graph
+        let sum: Value = (0..1000).map(|_| Value::from(1.0)).sum();
+        assert_eq!(sum.topological_order().len(), 1001);
+    }
+
+    #[test]
+    fn test_labels() {
+        let w = Value::named("w", 2.0);
+        let x = Value::from(3.0).with_label("x");
+        let y = (w.clone() * x.clone()).with_label("y");
+        let z = y.clone().relu();
+        assert_eq!(w.label(), Some("w".to_string()));
+        assert_eq!(z.label(), None);
+        assert!(std::rc::Rc::ptr_eq(&z.find("x").unwrap(), &x));
+        assert!(std::rc::Rc::ptr_eq(&z.find("y").unwrap(), &y));
+        assert_eq!(z.find("b"), None);
+    }
+
+    #[test]
+    fn test_sum_and_dot() {
+        let a = Value::from(2.0);
+        let b = Value::from(-3.0);
+        let c = Value::from(10.0);
+        let s: Value = [a.clone(), b.clone(), c.clone(), a.clone()]
+            .into_iter()
+            .sum();
+        assert_eq!(s.data(), 11.0);
+        assert_eq!(s.children().len(), 4);
+        let d = Value::dot(&[a.clone(), b.clone()], &[c.clone(), a.clone()]);
+        assert_eq!(d.data(), 14.0);
+        assert_eq!(d.children().len(), 4);
+
+        (s * d).backward();
+        assert_eq!(a.gradient(), 2.0 * 14.0 + (10.0 - 3.0) * 11.0);
+        assert_eq!(b.gradient(), 14.0 + 2.0 * 11.0);
+        assert_eq!(c.gradient(), 14.0 + 2.0 * 11.0);
+    }
+
+    #[test]
+    fn test_empty_sum_and_product() {
+        assert_eq!(std::iter::empty::<Value>().sum::<Value>().data(), 0.0);
+        assert_eq!(std::iter::empty::<Value>().product::<Value>().data(), 1.0);
+        let p: Value = [2.0, 3.0, 4.0].into_iter().map(Value::from).product();
+        assert_eq!(p.data(), 24.0);
+    }
+
+    #[test]
+    fn test_sub_neg_div() {
+        let a = Value::from(-4.0);
+        let b = Value::from(2.0);
+        let c = (a.clone() - b.clone()) / -(b.clone() * 0.5);
+        assert_eq!(c.data(), 6.0);
+        // a - b, b * 0.5, its negation and the division: one node each
+        assert_eq!(c.topological_order().len(), 7);
+        c.backward();
+        assert_eq!(a.gradient(), -1.0);
+        // dc/db = -1 / -b/2 - (a - b) / (b/2)^2 * -1/2
+        assert_eq!(b.gradient(), 1.0 - 3.0);
+    }
 }